@@ -0,0 +1,7 @@
+-- Add up migration script here
+CREATE TABLE image_mirror (
+    image_id INTEGER NOT NULL,
+    host TEXT NOT NULL,
+    url TEXT NOT NULL,
+    PRIMARY KEY (image_id, host)
+);
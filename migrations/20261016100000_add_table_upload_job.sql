@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS upload_job (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    gallery_id INTEGER NOT NULL,
+    token TEXT NOT NULL,
+    check_exist BOOLEAN NOT NULL,
+    state TEXT NOT NULL,
+    error TEXT,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+-- 同一个画廊同时只允许存在一个未结束的任务
+CREATE UNIQUE INDEX upload_job_gallery_id_active_idx ON upload_job (gallery_id) WHERE state NOT IN ('done', 'failed');
@@ -0,0 +1,10 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS gallery_failure (
+    gallery_id INTEGER PRIMARY KEY NOT NULL,
+    token TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    last_error TEXT NOT NULL,
+    next_retry_at DATETIME NOT NULL,
+    parked BOOLEAN NOT NULL,
+    updated_at DATETIME NOT NULL
+);
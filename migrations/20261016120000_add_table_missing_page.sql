@@ -0,0 +1,9 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS missing_page (
+    gallery_id INTEGER NOT NULL,
+    page INTEGER NOT NULL,
+    hash TEXT NOT NULL,
+    error TEXT NOT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (gallery_id, page)
+);
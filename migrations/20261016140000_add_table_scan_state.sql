@@ -0,0 +1,6 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS scan_state (
+    profile TEXT PRIMARY KEY NOT NULL,
+    max_gallery_id INTEGER NOT NULL,
+    updated_at DATETIME NOT NULL
+);
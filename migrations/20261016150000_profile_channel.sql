@@ -0,0 +1,17 @@
+-- Add up migration script here
+-- 投票按频道区分，已有的投票归属于对应画廊最早发布的频道
+ALTER TABLE poll ADD COLUMN channel_id TEXT;
+UPDATE poll SET channel_id = (
+    SELECT message.channel_id
+    FROM message
+    WHERE message.gallery_id = poll.gallery_id
+    ORDER BY message.publish_date
+    LIMIT 1
+);
+CREATE INDEX poll_channel_id_idx ON poll (channel_id);
+
+-- 上传任务记录目标频道，为空时发布到默认频道
+ALTER TABLE upload_job ADD COLUMN channel_id TEXT;
+
+-- 失败的画廊由原来的搜索配置负责重试
+ALTER TABLE gallery_failure ADD COLUMN profile TEXT NOT NULL DEFAULT 'default';
@@ -0,0 +1,7 @@
+-- Add up migration script here
+ALTER TABLE gallery ADD COLUMN category TEXT;
+ALTER TABLE gallery ADD COLUMN language TEXT;
+ALTER TABLE gallery ADD COLUMN uploader TEXT;
+ALTER TABLE gallery ADD COLUMN filesize INTEGER;
+ALTER TABLE gallery ADD COLUMN rating REAL;
+ALTER TABLE gallery ADD COLUMN rating_count INTEGER;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS comment (
+    gallery_id INTEGER NOT NULL,
+    comment_id INTEGER NOT NULL,
+    author TEXT NOT NULL,
+    score INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    posted DATETIME NOT NULL,
+    PRIMARY KEY (gallery_id, comment_id)
+);
@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE gallery ADD COLUMN next_check_at DATETIME;
+-- 已有的画廊分散到接下来的两周内检查，避免启动后同时检查所有画廊
+UPDATE gallery SET next_check_at = datetime('now', '+' || (id % 14) || ' days');
+CREATE INDEX gallery_next_check_at_idx ON gallery (next_check_at);
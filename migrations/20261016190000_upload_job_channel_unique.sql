@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- 同一个画廊可以同时有发布到不同频道的任务
+DROP INDEX IF EXISTS upload_job_gallery_id_active_idx;
+CREATE UNIQUE INDEX upload_job_gallery_channel_active_idx ON upload_job (gallery_id, IFNULL(channel_id, '')) WHERE state NOT IN ('done', 'failed');
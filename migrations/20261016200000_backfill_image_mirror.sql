@@ -0,0 +1,8 @@
+-- Add up migration script here
+-- 镜像功能之前上传的图片没有镜像记录，把它们的主地址补上，之后补充的镜像才能和主地址互相切换
+INSERT OR IGNORE INTO image_mirror (image_id, host, url)
+SELECT
+    id,
+    CASE WHEN url LIKE '/file/%' THEN 'telegraph' ELSE 'teletype' END,
+    CASE WHEN url LIKE '/file/%' THEN 'https://telegra.ph' || url ELSE url END
+FROM image;
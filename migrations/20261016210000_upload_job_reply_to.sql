@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- 新版本画廊的消息需要回复旧版本的消息，重启后继续任务时也要用到
+ALTER TABLE upload_job ADD COLUMN reply_to INTEGER;
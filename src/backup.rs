@@ -15,16 +15,11 @@ use crate::bot::Bot;
 pub struct BackupService {
     config: BackupConfig,
     bot: Bot,
-    database_path: String,
 }
 
 impl BackupService {
-    pub fn new(config: BackupConfig, bot: Bot, database_path: String) -> Self {
-        Self {
-            config,
-            bot,
-            database_path,
-        }
+    pub fn new(config: BackupConfig, bot: Bot) -> Self {
+        Self { config, bot }
     }
 
     /// 启动定时备份服务
@@ -389,7 +384,7 @@ impl BackupService {
         
         let output = tokio::process::Command::new("sqlite3")
             .arg(&db_path)
-            .arg(&format!(".backup {}", backup_path.display()))
+            .arg(format!(".backup {}", backup_path.display()))
             .output()
             .await;
 
@@ -489,11 +484,7 @@ pub async fn start_backup_service(backup_config: &crate::config::Backup, bot: Bo
         }
     }
 
-    let backup_service = BackupService::new(
-        backup_config.clone(),
-        bot,
-        "db.sqlite".to_string(), // 使用默认数据库路径
-    );
+    let backup_service = BackupService::new(backup_config.clone(), bot);
 
     // 直接运行备份服务，不再spawn新任务
     backup_service.start().await
@@ -4,8 +4,9 @@ use std::time::Duration;
 use anyhow::Result;
 use chrono::Timelike;
 use clap::Parser;
-use exloli_next::config::Config;
-use exloli_next::ehentai::EhClient;
+use exloli_next::config::{Config, EH_SITE};
+use exloli_next::ehentai::{site_url, EhClient};
+use exloli_next::utils::http::HttpFactory;
 use futures::StreamExt;
 use glob::glob;
 use tracing::{info, warn};
@@ -28,6 +29,7 @@ async fn main() -> Result<()> {
     let args = Args::parse();
 
     let config = Config::new(&args.config)?;
+    EH_SITE.set(config.exhentai.site.trim_end_matches('/').to_string()).unwrap();
 
     env::set_var("RUST_LOG", &config.log_level);
 
@@ -36,9 +38,11 @@ async fn main() -> Result<()> {
         .try_init()
         .unwrap();
 
-    let ehentai = EhClient::new(&config.exhentai.cookie).await?;
+    let http = HttpFactory::new(&config);
+    let ehentai = EhClient::new(&http, &config.exhentai).await?;
     let params = [("favcat", args.favcat)];
-    let stream = ehentai.page_iter("https://exhentai.org/favorites.php", &params);
+    let favorites = format!("{}/favorites.php", site_url());
+    let stream = ehentai.page_iter(&favorites, &params);
     tokio::pin!(stream);
     while let Some(gallery) = stream.next().await {
         if glob(&format!("{}/*[[]{}]", args.download, gallery.id()))?.next().is_some() {
@@ -2,10 +2,11 @@ use std::env;
 
 use anyhow::Result;
 use exloli_next::bot::start_dispatcher;
-use exloli_next::config::{Config, CHANNEL_ID};
+use exloli_next::config::{Config, EH_SITE};
 use exloli_next::ehentai::EhClient;
 use exloli_next::tags::EhTagTransDB;
 use exloli_next::uploader::ExloliUploader;
+use exloli_next::utils::http::HttpFactory;
 use exloli_next::backup::start_backup_service;
 use futures::FutureExt;
 use teloxide::prelude::*;
@@ -24,7 +25,7 @@ async fn main() -> Result<()> {
 
 pub async fn run_app() -> Result<()> {
     let config = Config::new("./config.toml")?;
-    CHANNEL_ID.set(config.telegram.channel_id.to_string()).unwrap();
+    EH_SITE.set(config.exhentai.site.trim_end_matches('/').to_string()).unwrap();
 
     // NOTE: 全局数据库连接需要用这个变量初始化
     env::set_var("DATABASE_URL", &config.database_url);
@@ -35,8 +36,9 @@ pub async fn run_app() -> Result<()> {
         .try_init()
         .unwrap();
 
-    let trans = EhTagTransDB::new(&config.exhentai.trans_file);
-    let ehentai = EhClient::new(&config.exhentai.cookie).await?;
+    let http = HttpFactory::new(&config);
+    let trans = EhTagTransDB::new(&config.exhentai.trans_file, http.clone());
+    let ehentai = EhClient::new(&http, &config.exhentai).await?;
     let bot = Bot::new(&config.telegram.token)
         .throttle(Default::default())
         .parse_mode(ParseMode::Html)
@@ -84,8 +86,10 @@ pub async fn run_app() -> Result<()> {
             // Bot调度器任务，如果失败尝试重启
             loop {
                 // start_dispatcher 永不返回，如果返回则说明出错了
-                start_dispatcher(config.clone(), uploader.clone(), bot.clone(), trans.clone()).await;
-                tracing::error!("Bot调度器意外退出，5秒后重新启动");
+                match start_dispatcher(config.clone(), uploader.clone(), bot.clone(), trans.clone()).await {
+                    Ok(_) => tracing::error!("Bot调度器意外退出，5秒后重新启动"),
+                    Err(e) => tracing::error!("Bot调度器启动失败，请检查代理配置: {}，5秒后重试", e),
+                }
                 tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
             }
         })
@@ -21,6 +21,16 @@ pub enum AdminCommand {
     ReCheck,
     #[command(description = "手动备份数据库")]
     Backup,
+    #[command(description = "恢复指定画廊的自动上传，不带参数时列出所有被暂停的画廊")]
+    Unpark(String),
+    #[command(description = "标记图片，用法：/flag <ad|invalid> <页面 URL 或图片 hash>...")]
+    Flag(String),
+    #[command(description = "取消图片标记，参数为页面 URL 或图片 hash")]
+    Unflag(String),
+    #[command(description = "查看图片额度和上传队列状态")]
+    Status,
+    #[command(description = "无视扫描进度，回溯搜索结果的前 N 个画廊，可以指定搜索配置名称")]
+    Backfill(String),
 }
 
 #[derive(BotCommands, Clone, PartialEq, Debug)]
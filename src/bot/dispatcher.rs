@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+use anyhow::Result;
 use teloxide::prelude::*;
 
 use super::filter::{filter_callbackdata, filter_channel_msg};
@@ -10,13 +11,14 @@ use crate::bot::scheduler::Scheduler;
 use crate::config::Config;
 use crate::tags::EhTagTransDB;
 use crate::uploader::ExloliUploader;
+use crate::utils::http::{ClientKind, HttpFactory};
 
 pub async fn start_dispatcher(
     config: Config,
     ehentai: ExloliUploader,
     bot: Bot,
     trans: EhTagTransDB,
-) {
+) -> Result<()> {
     let handler = dptree::entry()
         .branch(
             Update::filter_message()
@@ -36,7 +38,8 @@ pub async fn start_dispatcher(
 
     let challenge_locker = ChallengeLocker::new();
 
-    let challenge_provider = ChallengeProvider::new();
+    let client = HttpFactory::new(&config).client(ClientKind::Other)?;
+    let challenge_provider = ChallengeProvider::new(client);
 
     let scheduler = Scheduler::new(bot.clone());
 
@@ -56,4 +59,5 @@ pub async fn start_dispatcher(
         .build()
         .dispatch()
         .await;
+    Ok(())
 }
@@ -36,8 +36,11 @@ async fn callback_challenge(
     if let Some((gallery, page, answer)) = locker.get_challenge(id) {
         let success = answer == artist;
         let gallery_entity = GalleryEntity::get(gallery).await?.context("找不到画廊")?;
-        let preview = gallery_preview_url(cfg.telegram.channel_id, gallery).await?;
-        let poll = PollEntity::get_by_gallery(gallery).await?.context("找不到投票")?;
+        let profile = cfg.profile_for_chat(&message.chat);
+        let preview = gallery_preview_url(&profile, gallery).await?;
+        let poll = PollEntity::get_by_gallery(&profile.channel_key(), gallery)
+            .await?
+            .context("找不到投票")?;
         ChallengeHistory::create(query.from.id.0 as i64, gallery, page, success, message.chat.id.0)
             .await?;
 
@@ -110,15 +113,14 @@ async fn callback_change_page(
         CallbackData::NextPage(from, to, offset) => (from, to, offset + 1),
         _ => unreachable!(),
     };
-    let text = cmd_best_text(from, to, offset, cfg.telegram.channel_id).await?;
+    let Some(message) = query.message else { return Ok(()) };
+    let text = cmd_best_text(from, to, offset, &cfg.profile_for_chat(&message.chat)).await?;
     let keyboard = cmd_best_keyboard(from, to, offset);
 
-    if let Some(message) = query.message {
-        bot.edit_message_text(message.chat.id, message.id, text)
-            .reply_markup(keyboard)
-            .disable_web_page_preview(true)
-            .await?;
-    }
+    bot.edit_message_text(message.chat.id, message.id, text)
+        .reply_markup(keyboard)
+        .disable_web_page_preview(true)
+        .await?;
 
     Ok(())
 }
@@ -3,19 +3,23 @@ use teloxide::dispatching::DpHandlerDescription;
 use teloxide::dptree::case;
 use teloxide::prelude::*;
 use teloxide::types::MessageId;
+use teloxide::utils::html::escape;
 use tracing::{info, warn};
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use crate::bot::command::AdminCommand;
 use crate::bot::filter::filter_admin_msg;
 use crate::bot::{Bot, ThrottledEditor};
-use crate::database::{GalleryEntity, MessageEntity};
-use crate::ehentai::EhGalleryUrl;
-use crate::uploader::{ExloliUploader, UploadProgress};
-
-use crate::config::Config;
+use crate::database::{
+    GalleryEntity, GalleryFailureEntity, ImageFlagEntity, ImageFlagKind, MessageEntity,
+    UploadJobEntity,
+};
+use crate::ehentai::{EhGalleryUrl, EhPageUrl};
+use crate::uploader::{ExloliUploader, UploadError, UploadProgress};
+
+use crate::config::{Config, Profile};
 use crate::{reply_to, try_with_reply};
 
 #[derive(Clone)]
@@ -34,10 +38,10 @@ pub struct GalleryProgress {
 pub enum UploadStage {
     Starting,
     Scanning,
-    Resolving,
     Uploading,
-    Publishing,
     Complete,
+    // 只在进度消息中通过 Debug 输出
+    #[allow(dead_code)]
     Failed(String),
 }
 
@@ -52,6 +56,11 @@ pub fn admin_command_handler() -> Handler<'static, DependencyMap, Result<()>, Dp
         .branch(case![AdminCommand::ReCheck].endpoint(cmd_recheck))
         .branch(case![AdminCommand::ReUpload].endpoint(cmd_reupload))
         .branch(case![AdminCommand::Backup].endpoint(cmd_backup))
+        .branch(case![AdminCommand::Unpark(urls)].endpoint(cmd_unpark))
+        .branch(case![AdminCommand::Flag(args)].endpoint(cmd_flag))
+        .branch(case![AdminCommand::Unflag(args)].endpoint(cmd_unflag))
+        .branch(case![AdminCommand::Status].endpoint(cmd_status))
+        .branch(case![AdminCommand::Backfill(count)].endpoint(cmd_backfill))
 }
 
 // TODO: 该功能需要移除
@@ -71,18 +80,22 @@ async fn cmd_upload(
     bot: Bot,
     msg: Message,
     uploader: ExloliUploader,
+    cfg: Config,
     urls: String,
 ) -> Result<()> {
-    cmd_upload_wrapper(bot, msg, uploader, urls, false).await
+    let profile = cfg.profile_for_chat(&msg.chat);
+    cmd_upload_wrapper(bot, msg, uploader, profile, urls, false).await
 }
 
 async fn cmd_force_upload(
     bot: Bot,
     msg: Message,
     uploader: ExloliUploader,
+    cfg: Config,
     urls: String,
 ) -> Result<()> {
-    cmd_upload_wrapper(bot, msg, uploader, urls, true).await
+    let profile = cfg.profile_for_chat(&msg.chat);
+    cmd_upload_wrapper(bot, msg, uploader, profile, urls, true).await
 }
 
 // Add a catch-all wrapper to handle any panics
@@ -90,10 +103,11 @@ async fn cmd_upload_wrapper(
     bot: Bot,
     msg: Message,
     uploader: ExloliUploader,
+    profile: Profile,
     urls: String,
     force: bool,
 ) -> Result<()> {
-    match cmd_upload_inner(bot.clone(), msg.clone(), uploader, urls, force).await {
+    match cmd_upload_inner(bot.clone(), msg.clone(), uploader, profile, urls, force).await {
         Ok(_) => {
             info!("Upload command completed successfully");
             Ok(())
@@ -110,6 +124,7 @@ async fn cmd_upload_inner(
     bot: Bot,
     msg: Message,
     uploader: ExloliUploader,
+    profile: Profile,
     urls: String,
     force: bool,
 ) -> Result<()> {
@@ -181,7 +196,6 @@ async fn cmd_upload_inner(
             Duration::from_secs(5)
         ));
         
-        let bot_clone = bot.clone();
         let msg_clone = msg.clone();
         let progress_msg_id = progress_msg.id;
         let progress_clone = progress.clone();
@@ -191,17 +205,16 @@ async fn cmd_upload_inner(
         let results_clone = results.clone();
         let editor_clone = throttled_editor.clone();
         let callback = Arc::new(move |prog: GalleryProgress| {
-            let bot = bot_clone.clone();
             let msg = msg_clone.clone();
             let galleries = galleries_clone.clone();
             let results = results_clone.clone();
             let editor = editor_clone.clone();
             async move {
-                update_gallery_progress_with_editor(&bot, msg.chat.id, progress_msg_id, index, &galleries, &results, &prog, editor).await.ok();
+                update_gallery_progress_with_editor(msg.chat.id, progress_msg_id, index, &galleries, &results, &prog, editor).await.ok();
             }
         });
         
-        match upload_with_progress_new(&uploader, gallery, !force, progress_clone, callback).await {
+        match upload_with_progress_new(&uploader, gallery, !force, &profile, progress_clone, callback).await {
             Ok(_) => {
                 info!("Upload successful for gallery {}", gallery.id());
                 results.push((gallery.id(), true, "上传成功".to_string()));
@@ -235,10 +248,10 @@ async fn cmd_upload_inner(
 }
 
 fn create_progress_bar(current: usize, total: usize, results: &[(i32, bool, String)]) -> String {
-    let progress = if total > 0 { (current * 10) / total } else { 0 };
+    let progress = (current * 10).checked_div(total).unwrap_or(0);
     let filled = "█".repeat(progress);
     let empty = "░".repeat(10 - progress);
-    let percentage = if total > 0 { (current * 100) / total } else { 0 };
+    let percentage = (current * 100).checked_div(total).unwrap_or(0);
     
     let mut text = format!("进度: [{}{}] {}% ({}/{})\n\n", filled, empty, percentage, current, total);
     
@@ -272,6 +285,7 @@ async fn upload_with_progress_new<F, Fut>(
     uploader: &ExloliUploader, 
     gallery_url: &EhGalleryUrl, 
     check: bool,
+    profile: &Profile,
     progress: Arc<Mutex<GalleryProgress>>,
     callback: Arc<F>
 ) -> Result<()> 
@@ -282,7 +296,7 @@ where
     // 检查是否需要上传
     if check 
         && GalleryEntity::check(gallery_url.id()).await?
-        && MessageEntity::get_by_gallery(gallery_url.id()).await?.is_some()
+        && MessageEntity::get_by_gallery(&profile.channel_key(), gallery_url.id()).await?.is_some()
     {
         let mut prog = progress.lock().await;
         prog.current_stage = UploadStage::Complete;
@@ -313,7 +327,7 @@ where
         }
         
         // 2. 删除消息记录，这样会重新发布新消息
-        if let Err(e) = MessageEntity::delete_by_gallery(gallery_id).await {
+        if let Err(e) = MessageEntity::delete_by_gallery(&profile.channel_key(), gallery_id).await {
             warn!("删除消息记录失败: {}", e);
         }
         
@@ -363,7 +377,7 @@ where
     };
 
     // 调用带进度回调的上传方法
-    match uploader.try_upload_with_progress(gallery_url, check, Some(progress_mapper)).await {
+    match uploader.try_upload_with_progress(gallery_url, check, profile, Some(progress_mapper)).await {
         Ok(_) => {
             let mut prog = progress.lock().await;
             prog.current_stage = UploadStage::Complete;
@@ -373,10 +387,21 @@ where
         }
         Err(e) => {
             let mut prog = progress.lock().await;
+            let hint = match &e {
+                UploadError::Telegram(_) => "请检查 bot 在频道中的权限",
+                UploadError::HostRejected { .. } => "请检查图床配置",
+                UploadError::Config(_) => "请检查代理配置",
+                _ if e.blocked().is_some() => "E 站访问受限，请检查 cookie 和图片额度",
+                _ if e.is_retryable() => "可以稍后重试",
+                _ => "重试无效，需要手动处理",
+            };
+            prog.status_message = match e.page() {
+                Some(page) => format!("第 {} 页处理失败，{}", page, hint),
+                None => format!("上传失败，{}", hint),
+            };
             prog.current_stage = UploadStage::Failed(e.to_string());
-            prog.status_message = format!("上传失败: {}", e);
             callback(prog.clone()).await;
-            Err(e)
+            Err(e.into())
         }
     }
 }
@@ -384,7 +409,6 @@ where
 
 // 使用 ThrottledEditor 的画廊进度更新函数
 async fn update_gallery_progress_with_editor(
-    _bot: &Bot,
     chat_id: ChatId,
     message_id: MessageId,
     current_gallery_index: usize,
@@ -397,30 +421,6 @@ async fn update_gallery_progress_with_editor(
     editor.edit_message_throttled(chat_id, message_id, text).await.map_err(|e| anyhow::anyhow!("Failed to edit message: {}", e))
 }
 
-// 带时间间隔控制的画廊进度更新函数（保留作为备用）
-async fn update_gallery_progress_throttled(
-    bot: &Bot,
-    chat_id: ChatId,
-    message_id: MessageId,
-    current_gallery_index: usize,
-    all_galleries: &[EhGalleryUrl],
-    completed_results: &[(i32, bool, String)],
-    current_progress: &GalleryProgress,
-    last_update_time: Arc<Mutex<Instant>>
-) -> Result<()> {
-    // 检查是否需要限制更新频率
-    {
-        let mut last_update = last_update_time.lock().await;
-        let now = Instant::now();
-        if now.duration_since(*last_update) < Duration::from_secs(5) {
-            return Ok(()); // 跳过更新，避免频繁调用 API
-        }
-        *last_update = now;
-    }
-    
-    update_gallery_progress(bot, chat_id, message_id, current_gallery_index, all_galleries, completed_results, current_progress).await
-}
-
 // 生成画廊进度文本
 fn create_gallery_progress_text(
     current_gallery_index: usize,
@@ -459,7 +459,7 @@ fn create_gallery_progress_text(
         }
     }
     
-    text.push_str("\n");
+    text.push('\n');
     
     // 显示总体进度
     let overall_progress = create_progress_bar(current_gallery_index, all_galleries.len(), completed_results);
@@ -468,21 +468,6 @@ fn create_gallery_progress_text(
     text
 }
 
-// 更新画廊进度显示
-async fn update_gallery_progress(
-    bot: &Bot,
-    chat_id: ChatId,
-    message_id: MessageId,
-    current_gallery_index: usize,
-    all_galleries: &[EhGalleryUrl],
-    completed_results: &[(i32, bool, String)],
-    current_progress: &GalleryProgress
-) -> Result<()> {
-    let text = create_gallery_progress_text(current_gallery_index, all_galleries, completed_results, current_progress);
-    bot.edit_message_text(chat_id, message_id, text).await.ok();
-    Ok(())
-}
-
 // 创建页面级进度条
 fn create_page_progress_bar(current: usize, total: usize) -> String {
     if total == 0 {
@@ -500,11 +485,7 @@ fn create_page_progress_bar(current: usize, total: usize) -> String {
 async fn cmd_backup(bot: Bot, msg: Message, config: Config) -> Result<()> {
     info!("{}: /backup", msg.from().unwrap().id);
     
-    let backup_service = crate::backup::BackupService::new(
-        config.backup.clone(),
-        bot.clone(),
-        config.database_url.clone(),
-    );
+    let backup_service = crate::backup::BackupService::new(config.backup.clone(), bot.clone());
     
     match backup_service.manual_backup().await {
         Ok(result) => {
@@ -518,14 +499,180 @@ async fn cmd_backup(bot: Bot, msg: Message, config: Config) -> Result<()> {
     Ok(())
 }
 
-async fn cmd_delete(bot: Bot, msg: Message, command: AdminCommand) -> Result<()> {
+async fn cmd_status(bot: Bot, msg: Message, uploader: ExloliUploader) -> Result<()> {
+    info!("{}: /status", msg.from().unwrap().id);
+
+    // 优先获取最新的额度，失败时显示上一次的结果
+    uploader.refresh_quota().await;
+    let quota = match uploader.quota() {
+        Some((quota, time)) => format!(
+            "{}/{}，剩余 {}（更新于 {} UTC）",
+            quota.used,
+            quota.limit,
+            quota.remaining(),
+            time.format("%Y-%m-%d %H:%M")
+        ),
+        None => "未知".to_string(),
+    };
+    let jobs = UploadJobEntity::list_unfinished().await?.len();
+    let parked = GalleryFailureEntity::list_parked().await?.len();
+    let access = if uploader.is_blocked() { "受限" } else { "正常" };
+    let accounts = uploader
+        .accounts()
+        .iter()
+        .map(|(name, health)| format!("  {}：{}", escape(name), escape(&health.to_string())))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let text = format!(
+        "E 站访问：{}\n账号：\n{}\n图片额度：{}\n未完成的上传任务：{}\n被暂停的画廊：{}",
+        access, accounts, quota, jobs, parked
+    );
+    reply_to!(bot, msg, text).await?;
+    Ok(())
+}
+
+async fn cmd_backfill(
+    bot: Bot,
+    msg: Message,
+    uploader: ExloliUploader,
+    cfg: Config,
+    args: String,
+) -> Result<()> {
+    info!("{}: /backfill {}", msg.from().unwrap().id, args);
+    let mut args = args.split_whitespace();
+    let Some(Ok(count)) = args.next().map(str::parse::<usize>) else {
+        reply_to!(bot, msg, "用法：/backfill <画廊数量> [搜索配置名称]").await?;
+        return Ok(());
+    };
+    let profile = match args.next() {
+        Some(name) => match cfg.profiles().into_iter().find(|p| p.name == name) {
+            Some(profile) => profile,
+            None => {
+                reply_to!(bot, msg, format!("找不到搜索配置 {}", escape(name))).await?;
+                return Ok(());
+            }
+        },
+        None => cfg.profile_for_chat(&msg.chat),
+    };
+    let reply =
+        reply_to!(bot, msg, format!("开始回溯 {} 的前 {} 个画廊……", profile.name, count)).await?;
+    let text = match uploader.backfill(&profile, count).await {
+        Ok(total) => format!("回溯完成，共处理 {} 个画廊", total),
+        Err(e) => format!("回溯失败：{}", escape(&e.to_string())),
+    };
+    bot.edit_message_text(msg.chat.id, reply.id, text).await?;
+    Ok(())
+}
+
+async fn cmd_unpark(bot: Bot, msg: Message, urls: String) -> Result<()> {
+    info!("{}: /unpark {}", msg.from().unwrap().id, urls);
+
+    if urls.trim().is_empty() {
+        let parked = GalleryFailureEntity::list_parked().await?;
+        if parked.is_empty() {
+            reply_to!(bot, msg, "没有被暂停自动上传的画廊").await?;
+            return Ok(());
+        }
+        let mut text = format!("共有 {} 个画廊被暂停自动上传：\n\n", parked.len());
+        for failure in &parked {
+            text.push_str(&format!(
+                "{}\n失败 {} 次：{}\n\n",
+                failure.url(),
+                failure.attempts,
+                escape(&failure.last_error)
+            ));
+        }
+        reply_to!(bot, msg, text).await?;
+        return Ok(());
+    }
+
+    let mut count = 0;
+    for url in urls.split_whitespace() {
+        match url.parse::<EhGalleryUrl>() {
+            Ok(gallery) => {
+                count += GalleryFailureEntity::delete(gallery.id()).await?.rows_affected();
+            }
+            Err(e) => info!("Failed to parse URL {}: {}", url, e),
+        }
+    }
+    reply_to!(bot, msg, format!("已恢复 {} 个画廊的自动上传", count)).await?;
+    Ok(())
+}
+
+/// 从页面 URL 中提取图片 hash，不是 URL 时视为 hash 本身，hash 必须为 10 位小写十六进制
+fn parse_image_hash(s: &str) -> Option<String> {
+    let hash = match s.parse::<EhPageUrl>() {
+        Ok(page) => page.hash().to_string(),
+        Err(_) => s.to_string(),
+    };
+    let valid = hash.len() == 10 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    valid.then_some(hash)
+}
+
+/// 无法识别的参数的提示
+fn invalid_hash_text(invalid: &[&str]) -> String {
+    if invalid.is_empty() {
+        return String::new();
+    }
+    format!("\n无法识别的参数：{}", escape(&invalid.join(" ")))
+}
+
+async fn cmd_flag(bot: Bot, msg: Message, args: String) -> Result<()> {
+    info!("{}: /flag {}", msg.from().unwrap().id, args);
+
+    let mut args = args.split_whitespace();
+    let kind = match args.next() {
+        Some("ad") => ImageFlagKind::Ad,
+        Some("invalid") => ImageFlagKind::Invalid,
+        _ => {
+            reply_to!(bot, msg, "用法：/flag &lt;ad|invalid&gt; &lt;页面 URL 或图片 hash&gt;...").await?;
+            return Ok(());
+        }
+    };
+    let reason = format!("由 {} 手动标记", msg.from().unwrap().id);
+
+    let mut count = 0;
+    let mut invalid = vec![];
+    for arg in args {
+        match parse_image_hash(arg) {
+            Some(hash) => {
+                ImageFlagEntity::create(&hash, kind, &reason).await?;
+                count += 1;
+            }
+            None => invalid.push(arg),
+        }
+    }
+    reply_to!(bot, msg, format!("已标记 {} 张图片{}", count, invalid_hash_text(&invalid))).await?;
+    Ok(())
+}
+
+async fn cmd_unflag(bot: Bot, msg: Message, args: String) -> Result<()> {
+    info!("{}: /unflag {}", msg.from().unwrap().id, args);
+
+    let mut count = 0;
+    let mut invalid = vec![];
+    for arg in args.split_whitespace() {
+        match parse_image_hash(arg) {
+            Some(hash) => count += ImageFlagEntity::delete(&hash).await?.rows_affected(),
+            None => invalid.push(arg),
+        }
+    }
+    reply_to!(bot, msg, format!("已取消 {} 张图片的标记{}", count, invalid_hash_text(&invalid)))
+        .await?;
+    Ok(())
+}
+
+async fn cmd_delete(bot: Bot, msg: Message, cfg: Config, command: AdminCommand) -> Result<()> {
     info!("{}: /delete", msg.from().unwrap().id);
     let reply_to = msg.reply_to_message().context("没有回复消息")?;
 
     let channel = reply_to.forward_from_chat().context("该消息没有回复画廊")?;
     let channel_msg = reply_to.forward_from_message_id().context("获取转发来源失败")?;
 
-    let msg_entity = MessageEntity::get(channel_msg).await?.unwrap();
+    let channel_key = cfg.profile_for_chat(channel).channel_key();
+
+    let msg_entity = MessageEntity::get(&channel_key, channel_msg).await?.unwrap();
 
     bot.delete_message(reply_to.chat.id, reply_to.id).await?;
     bot.delete_message(channel.id, MessageId(msg_entity.id)).await?;
@@ -534,7 +681,7 @@ async fn cmd_delete(bot: Bot, msg: Message, command: AdminCommand) -> Result<()>
         GalleryEntity::update_deleted(msg_entity.gallery_id, true).await?;
     } else {
         GalleryEntity::delete(msg_entity.gallery_id).await?;
-        MessageEntity::delete(channel_msg).await?;
+        MessageEntity::delete(&channel_key, channel_msg).await?;
     }
 
     Ok(())
@@ -15,7 +15,8 @@ use crate::bot::command::{AdminCommand, PublicCommand};
 use crate::bot::filter::filter_admin_msg;
 use crate::bot::{ThrottledEditor};
 use crate::bot::handlers::{
-    cmd_best_keyboard, cmd_best_text, cmd_challenge_keyboard, gallery_preview_url,
+    cmd_best_keyboard, cmd_best_text, cmd_challenge_keyboard, gallery_meta_text,
+    gallery_preview_url,
 };
 use crate::bot::scheduler::Scheduler;
 use crate::bot::utils::{ChallengeLocker, ChallengeProvider};
@@ -65,10 +66,12 @@ async fn cmd_upload(
     bot: Bot,
     msg: Message,
     uploader: ExloliUploader,
+    cfg: Config,
     urls: String,
 ) -> Result<()> {
     let user_id = msg.from().unwrap().id;
     info!("{}: /upload {}", user_id, urls);
+    let profile = cfg.profile_for_chat(&msg.chat);
     
     if urls.trim().is_empty() {
         reply_to!(bot, msg, "请提供至少一个画廊链接").await?;
@@ -128,7 +131,7 @@ async fn cmd_upload(
             results.push((gallery.id(), false, "非管理员只能上传存在上传记录的画廊".to_string()));
         } else {
             // 创建进度跟踪（简化版本，因为公共命令权限限制）
-            match uploader.try_upload(gallery, true).await {
+            match uploader.try_upload(gallery, true, &profile).await {
                 Ok(_) => {
                     info!("Upload successful for gallery {}", gallery.id());
                     results.push((gallery.id(), true, "上传成功".to_string()));
@@ -163,10 +166,10 @@ async fn cmd_upload(
 }
 
 fn create_progress_bar_public(current: usize, total: usize, results: &[(i32, bool, String)]) -> String {
-    let progress = if total > 0 { (current * 10) / total } else { 0 };
+    let progress = (current * 10).checked_div(total).unwrap_or(0);
     let filled = "█".repeat(progress);
     let empty = "░".repeat(10 - progress);
-    let percentage = if total > 0 { (current * 100) / total } else { 0 };
+    let percentage = (current * 100).checked_div(total).unwrap_or(0);
     
     let mut text = format!("进度: [{}{}] {}% ({}/{})\n\n", filled, empty, percentage, current, total);
     
@@ -232,7 +235,7 @@ async fn cmd_best(
     scheduler: Scheduler,
 ) -> Result<()> {
     info!("{}: /best {} {}", msg.from().unwrap().id, end, start);
-    let text = cmd_best_text(start as i32, end as i32, 0, cfg.telegram.channel_id).await?;
+    let text = cmd_best_text(start as i32, end as i32, 0, &cfg.profile_for_chat(&msg.chat)).await?;
     let keyboard = cmd_best_keyboard(start as i32, end as i32, 0);
     let reply =
         reply_to!(bot, msg, text).reply_markup(keyboard).disable_web_page_preview(true).await?;
@@ -243,20 +246,30 @@ async fn cmd_best(
     Ok(())
 }
 
-async fn cmd_update(bot: Bot, msg: Message, uploader: ExloliUploader, url: String) -> Result<()> {
+async fn cmd_update(
+    bot: Bot,
+    msg: Message,
+    uploader: ExloliUploader,
+    cfg: Config,
+    url: String,
+) -> Result<()> {
     info!("{}: /update {}", msg.from().unwrap().id, url);
-    let msg_id = if url.is_empty() {
-        msg.reply_to_message()
-            .and_then(|msg| msg.forward_from_message_id())
-            .ok_or(anyhow!("Invalid URL"))?
+    // 回复的是频道转发的消息时使用该频道，否则使用当前群组对应的频道
+    let (msg_id, chat) = if url.is_empty() {
+        let reply = msg.reply_to_message().ok_or(anyhow!("Invalid URL"))?;
+        let msg_id = reply.forward_from_message_id().ok_or(anyhow!("Invalid URL"))?;
+        (msg_id, reply.forward_from_chat().unwrap_or(&msg.chat))
     } else {
-        Url::parse(&url)?
+        let msg_id = Url::parse(&url)?
             .path_segments()
-            .and_then(|p| p.last())
+            .and_then(|mut p| p.next_back())
             .and_then(|id| id.parse::<i32>().ok())
-            .ok_or(anyhow!("Invalid URL"))?
+            .ok_or(anyhow!("Invalid URL"))?;
+        (msg_id, &msg.chat)
     };
-    let msg_entity = MessageEntity::get(msg_id).await?.ok_or(anyhow!("Message not found"))?;
+    let channel = cfg.profile_for_chat(chat).channel_key();
+    let msg_entity =
+        MessageEntity::get(&channel, msg_id).await?.ok_or(anyhow!("Message not found"))?;
     let gl_entity =
         GalleryEntity::get(msg_entity.gallery_id).await?.ok_or(anyhow!("Gallery not found"))?;
 
@@ -285,19 +298,23 @@ async fn cmd_query(bot: Bot, msg: Message, cfg: Config, gallery: EhGalleryUrl) -
     info!("{}: /query {}", msg.from().unwrap().id, gallery);
     match GalleryEntity::get(gallery.id()).await? {
         Some(gallery) => {
-            let poll = PollEntity::get_by_gallery(gallery.id).await?.context("找不到投票")?;
-            let preview = gallery_preview_url(cfg.telegram.channel_id, gallery.id).await?;
+            let profile = cfg.profile_for_chat(&msg.chat);
+            let poll = PollEntity::get_by_gallery(&profile.channel_key(), gallery.id)
+                .await?
+                .context("找不到投票")?;
+            let preview = gallery_preview_url(&profile, gallery.id).await?;
             let url = gallery.url().url();
-            reply_to!(
-                bot,
-                msg,
-                format!(
-                    "消息：{preview}\n地址：{url}\n评分：{:.2}（{:.2}）",
-                    poll.score * 100.,
-                    poll.rank().await? * 100.
-                )
-            )
-            .await?;
+            let mut text = format!(
+                "消息：{preview}\n地址：{url}\n评分：{:.2}（{:.2}）",
+                poll.score * 100.,
+                poll.rank().await? * 100.
+            );
+            let meta = gallery_meta_text(&gallery);
+            if !meta.is_empty() {
+                text.push('\n');
+                text.push_str(&meta);
+            }
+            reply_to!(bot, msg, text).await?;
         }
         None => {
             reply_to!(bot, msg, "未找到").await?;
@@ -4,32 +4,29 @@ use tracing::info;
 
 use crate::bot::handlers::utils;
 use crate::bot::Bot;
-use crate::database::{GalleryEntity, PollEntity};
+use crate::config::Config;
+use crate::database::{CommentEntity, GalleryEntity, PollEntity};
 use crate::reply_to;
-
-pub async fn custom_pool_sender(bot: Bot, message: Message) -> Result<()> {
+use crate::uploader::ExloliUploader;
+
+pub async fn custom_pool_sender(
+    bot: Bot,
+    message: Message,
+    cfg: Config,
+    uploader: ExloliUploader,
+) -> Result<()> {
     info!("频道消息更新，发送投票");
 
     let msg_id = message.forward_from_message_id().context("找不到消息")?;
-    let gallery = GalleryEntity::get_by_msg(msg_id).await?.context("找不到画廊")?;
-
-    // FIXME: 此处如果父画廊还没有记录，则无法找到投票，应该改成不断向 E 站请求父画廊直到有父画廊存在投票或者没有父画廊为止
-    // 对于投票的 ID，如果该画廊有投票，则使用该画廊的投票 ID
-    let poll_id = match PollEntity::get_by_gallery(gallery.id).await? {
-        Some(v) => v.id,
-        // 如果没有，则尝试使用其父画廊的投票 ID
-        None => match gallery.parent {
-            Some(id) => match PollEntity::get_by_gallery(id).await? {
-                Some(v) => v.id,
-                // 如果还是没有，则使用其画廊 ID
-                None => gallery.id as i64,
-            },
-            None => gallery.id as i64,
-        },
-    };
+    let channel = message.forward_from_chat().context("找不到频道")?;
+    let channel = cfg.profile_for_chat(channel).channel_key();
+    let gallery = GalleryEntity::get_by_msg(&channel, msg_id).await?.context("找不到画廊")?;
+
+    // 该画廊或者它的某个祖先画廊已经有投票时沿用它，否则分配一个新的 ID
+    let poll_id = uploader.resolve_poll(&channel, &gallery).await?;
 
     // 此处存在重复插入，但可以忽略
-    PollEntity::create(poll_id, gallery.id).await?;
+    PollEntity::create(&channel, poll_id, gallery.id).await?;
 
     let votes = PollEntity::get_vote(poll_id).await?;
     let markup = utils::poll_keyboard(poll_id, &votes);
@@ -40,6 +37,16 @@ pub async fn custom_pool_sender(bot: Bot, message: Message) -> Result<()> {
         .reply_markup(markup)
         .await?;
 
+    if cfg.comment.post_top > 0 {
+        let comments =
+            CommentEntity::list_top(gallery.id, cfg.comment.min_score, cfg.comment.post_top).await?;
+        if !comments.is_empty() {
+            reply_to!(bot, message, utils::comments_text(&comments))
+                .disable_web_page_preview(true)
+                .await?;
+        }
+    }
+
     tokio::spawn(async move {
         // 辣鸡 tg 安卓客户端在置顶消息过多时似乎在进群时会卡住
         // 因此取消置顶频道自动转发的消息
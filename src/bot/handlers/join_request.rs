@@ -19,7 +19,8 @@ pub async fn join_request_handler(bot: Bot, jq: ChatJoinRequest, cfg: Config) ->
     }
 
     if let Some(invite_link) = jq.invite_link {
-        InviteLink::create(jq.from.id.0 as i64, &invite_link.invite_link).await?;
+        let channel = cfg.profile_for_chat(&jq.chat).channel_key();
+        InviteLink::create(jq.from.id.0 as i64, &channel, &invite_link.invite_link).await?;
     }
 
     info!("{}: 批准来自 {} 的加入请求", jq.chat.id, jq.from.id);
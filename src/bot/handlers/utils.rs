@@ -5,10 +5,13 @@ use teloxide::prelude::*;
 use teloxide::types::{
     InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup, MessageId, Recipient,
 };
-use teloxide::utils::html::link;
+use teloxide::utils::html::{escape, link};
 
 use crate::bot::utils::CallbackData;
-use crate::database::{ChallengeView, GalleryEntity, MessageEntity, TelegraphEntity};
+use crate::config::Profile;
+use crate::database::{
+    ChallengeView, CommentEntity, GalleryEntity, MessageEntity, TelegraphEntity,
+};
 use crate::tags::EhTagTransDB;
 
 pub fn cmd_challenge_keyboard(
@@ -24,19 +27,16 @@ pub fn cmd_challenge_keyboard(
     }))
 }
 
-pub async fn cmd_best_text(
-    start: i32,
-    end: i32,
-    offset: i32,
-    channel: Recipient,
-) -> Result<String> {
+pub async fn cmd_best_text(start: i32, end: i32, offset: i32, profile: &Profile) -> Result<String> {
     let start = Utc::now().date_naive() - Duration::days(start as i64);
     let end = Utc::now().date_naive() - Duration::days(end as i64);
 
     let mut text = format!("最近 {start} ~ {end} 天的本子排名（{offset}）");
 
-    for (score, title, gid) in GalleryEntity::list(start, end, 20, offset).await? {
-        let url = gallery_preview_url(channel.clone(), gid).await?;
+    for (score, title, gid) in
+        GalleryEntity::list(&profile.channel_key(), start, end, 20, offset).await?
+    {
+        let url = gallery_preview_url(profile, gid).await?;
         text.push_str(&format!("\n<code>{:.2}</code> - {}", score * 100., link(&url, &title),));
     }
 
@@ -84,12 +84,86 @@ pub fn poll_keyboard(poll_id: i64, votes: &[i32; 5]) -> InlineKeyboardMarkup {
     InlineKeyboardMarkup::new(options)
 }
 
-pub async fn gallery_preview_url(channel_id: Recipient, gallery_id: i32) -> Result<String> {
-    if let Some(msg) = MessageEntity::get_by_gallery(gallery_id).await? {
-        return Ok(url_of(channel_id, msg.id).to_string());
+/// 画廊在指定频道中的消息链接，没有发布到该频道时返回 telegraph 文章链接
+pub async fn gallery_preview_url(profile: &Profile, gallery_id: i32) -> Result<String> {
+    if let Some(msg) = MessageEntity::get_by_gallery(&profile.channel_key(), gallery_id).await? {
+        return Ok(url_of(profile.channel_id.clone(), msg.id).to_string());
     }
     if let Some(telehraph) = TelegraphEntity::get(gallery_id).await? {
         return Ok(telehraph.url);
     }
     Err(anyhow!("找不到画廊"))
 }
+
+/// 画廊的分类、语言、上传者、大小和 E 站评分，每项一行，旧画廊缺少的项会被省略
+pub fn gallery_meta_text(gallery: &GalleryEntity) -> String {
+    let mut lines = vec![];
+    match (&gallery.category, &gallery.language) {
+        (Some(category), Some(language)) => lines.push(format!("分类：{} / {}", category, language)),
+        (Some(category), None) => lines.push(format!("分类：{}", category)),
+        _ => {}
+    }
+    if let Some(uploader) = &gallery.uploader {
+        lines.push(format!("上传者：{}", escape(uploader)));
+    }
+    if let Some(size) = gallery.filesize {
+        lines.push(format!("大小：{:.1} MB，共 {} 页", size as f64 / 1024. / 1024., gallery.pages));
+    }
+    match (gallery.rating, gallery.rating_count) {
+        (Some(rating), Some(count)) => lines.push(format!("E 站评分：{:.2}（{} 人）", rating, count)),
+        (Some(rating), None) => lines.push(format!("E 站评分：{:.2}", rating)),
+        _ => {}
+    }
+    lines.join("\n")
+}
+
+/// E 站评论的展示格式，过长的评论会被截断，总长度也不会超过 Telegram 的消息长度限制
+pub fn comments_text(comments: &[CommentEntity]) -> String {
+    const MAX_CHARS: usize = 300;
+    // Telegram 限制为 4096 个字符，这里按包含 HTML 标签的长度计算，留出一些余量
+    const MAX_TOTAL: usize = 4000;
+    let mut text = String::from("E 站评论：");
+    let mut total = text.chars().count();
+    for comment in comments {
+        let mut content = comment.content.chars().take(MAX_CHARS).collect::<String>();
+        if comment.content.chars().count() > MAX_CHARS {
+            content.push('…');
+        }
+        let author = comment.author.chars().take(64).collect::<String>();
+        let entry = format!(
+            "\n\n<b>{}</b>（{:+}）：\n<blockquote>{}</blockquote>",
+            escape(&author),
+            comment.score,
+            escape(&content)
+        );
+        let len = entry.chars().count();
+        if total + len > MAX_TOTAL {
+            break;
+        }
+        total += len;
+        text.push_str(&entry);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_text_limit() {
+        let comments = (0..20)
+            .map(|i| CommentEntity {
+                gallery_id: 1,
+                comment_id: i,
+                author: "reader".into(),
+                score: 10,
+                content: "<&>".repeat(1000),
+                posted: Default::default(),
+            })
+            .collect::<Vec<_>>();
+        let text = comments_text(&comments);
+        assert!(text.chars().count() <= 4000);
+        assert!(text.contains("…</blockquote>"));
+    }
+}
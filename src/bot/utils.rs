@@ -4,7 +4,7 @@ use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use dashmap::DashMap;
-use image::EncodableLayout;
+use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use teloxide::prelude::*;
 use tokio::sync::mpsc::{channel, Receiver};
@@ -12,7 +12,8 @@ use tokio::sync::Mutex;
 use tokio::time::sleep;
 use tracing::{info, warn};
 
-use crate::database::ChallengeView;
+use crate::database::{ChallengeView, ImageFlagEntity, ImageFlagKind};
+use crate::utils::has_qrcode;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CallbackData {
@@ -126,11 +127,11 @@ impl ChallengeLocker {
 pub struct ChallengeProvider(Arc<Mutex<Receiver<Vec<ChallengeView>>>>);
 
 impl ChallengeProvider {
-    pub fn new() -> Self {
+    pub fn new(client: Client) -> Self {
         let (tx, rx) = channel(5);
         tokio::spawn(async move {
             loop {
-                match Self::_get_challenge().await {
+                match Self::_get_challenge(&client).await {
                     Ok(challenge) => {
                         // 处理发送错误,如果接收端关闭则退出循环
                         if tx.send(challenge).await.is_err() {
@@ -149,7 +150,7 @@ impl ChallengeProvider {
         Self(Arc::new(Mutex::new(rx)))
     }
 
-    async fn _get_challenge() -> Result<Vec<ChallengeView>> {
+    async fn _get_challenge(client: &Client) -> Result<Vec<ChallengeView>> {
         loop {
             let challenge = ChallengeView::get_random().await?;
             if challenge.is_empty() {
@@ -158,10 +159,13 @@ impl ChallengeProvider {
             }
             let answer = &challenge[0];
             let url = answer.url.clone();
-            let resp = reqwest::get(&url).await?;
+            let resp = client.get(&url).send().await?;
             let data = resp.bytes().await?;
             if has_qrcode(&data)? {
                 info!("跳过包含二维码的图片");
+                // 标记之后，该图片不会再出现在挑战中
+                ImageFlagEntity::create_by_image_id(answer.image_id, ImageFlagKind::Ad, "包含二维码")
+                    .await?;
                 continue;
             }
             return Ok(challenge);
@@ -172,10 +176,3 @@ impl ChallengeProvider {
         self.0.lock().await.recv().await
     }
 }
-
-pub fn has_qrcode(data: &[u8]) -> Result<bool> {
-    let image = image::load_from_memory(data)?.into_luma8();
-    let mut decoder = quircs::Quirc::default();
-    let codes = decoder.identify(image.width() as usize, image.height() as usize, image.as_bytes());
-    Ok(codes.count() > 0)
-}
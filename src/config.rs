@@ -1,17 +1,31 @@
+use std::collections::HashMap;
 use std::time::Duration;
 
-use anyhow::Result;
-use duration_str::deserialize_duration;
+use anyhow::{bail, Result};
+use duration_str::{deserialize_duration, deserialize_option_duration};
 use once_cell::sync::OnceCell;
 use serde::Deserialize;
-use teloxide::types::{ChatId, Recipient};
+use teloxide::types::{Chat, ChatId, Recipient};
 
-pub static CHANNEL_ID: OnceCell<String> = OnceCell::new();
+/// E 站地址，末尾不带斜杠
+pub static EH_SITE: OnceCell<String> = OnceCell::new();
 
 fn default_allow_public_commands() -> bool {
     true
 }
 
+fn default_site() -> String {
+    "https://exhentai.org".to_string()
+}
+
+fn default_account_cooldown() -> Duration {
+    Duration::from_secs(3600)
+}
+
+fn default_image_hosts() -> Vec<ImageHostKind> {
+    vec![ImageHostKind::Teletype]
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     /// 日志等级
@@ -23,26 +37,175 @@ pub struct Config {
     pub interval: Duration,
     /// Sqlite 数据库位置
     pub database_url: String,
-    /// 代理设置
+    /// 代理设置，支持 http、https 和 socks5
     pub proxy: Option<String>,
+    /// 下载图片时使用的代理，未设置时使用 proxy
+    pub image_proxy: Option<String>,
     pub exhentai: ExHentai,
     pub telegraph: Telegraph,
     pub telegram: Telegram,
-    pub s3: S3,
-    pub teletype: Teletype,
+    /// 使用的图床，每张图片都会上传到所有图床，第一个为主图床
+    #[serde(default = "default_image_hosts")]
+    pub image_hosts: Vec<ImageHostKind>,
+    pub s3: Option<S3>,
+    pub teletype: Option<Teletype>,
+    /// 大图片的本地重新编码设置
+    #[serde(default)]
+    pub transcode: Transcode,
+    /// 自动上传失败后的重试策略
+    #[serde(default)]
+    pub retry: Retry,
+    /// 少数页面无法下载时的处理方式
+    #[serde(default)]
+    pub partial_publish: PartialPublish,
+    /// 自动上传前的内容过滤规则
+    #[serde(default)]
+    pub filter: Filter,
+    /// 广告图片的自动识别
+    #[serde(default)]
+    pub ad_image: AdImage,
+    /// 图片额度检查
+    #[serde(default)]
+    pub quota: Quota,
+    /// 在讨论组中转发 E 站评论
+    #[serde(default)]
+    pub comment: Comment,
+    /// 搜索配置，每个配置有自己的搜索参数和发布频道，为空时使用 [exhentai] 和 [telegram] 中的设置
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
     pub backup: Backup,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ExHentai {
+    /// 站点地址，可以是里站、表站或者用于测试的本地服务
+    #[serde(default = "default_site")]
+    pub site: String,
+    /// 登陆 cookie，只有一个账号时可以只填写这一项
+    #[serde(default)]
+    pub cookie: String,
+    /// 多个账号，按顺序使用，当前账号受限时自动切换到下一个
+    #[serde(default)]
+    pub accounts: Vec<Account>,
+    /// 账号被封禁或者额度用完之后，多久重新启用
+    #[serde(default = "default_account_cooldown", deserialize_with = "deserialize_duration")]
+    pub account_cooldown: Duration,
+    /// 搜索参数，配置了 profiles 时不使用
+    #[serde(default)]
+    pub search_params: Vec<(String, String)>,
+    /// 最大遍历画廊数量，配置了 profiles 时不使用
+    #[serde(default)]
+    pub search_count: usize,
+    /// 翻译文件的位置
+    pub trans_file: String,
+}
+
+impl Config {
+    /// 所有搜索配置，未设置的项会使用全局设置补全
+    ///
+    /// 第一个为默认配置，手动上传以及无法判断来源的请求使用它的频道
+    pub fn profiles(&self) -> Vec<Profile> {
+        let mut profiles = self.profiles.clone();
+        if profiles.is_empty() {
+            profiles.push(Profile {
+                name: "default".to_string(),
+                search_params: self.exhentai.search_params.clone(),
+                search_count: self.exhentai.search_count,
+                interval: None,
+                filter: None,
+                channel_id: self.telegram.channel_id.clone(),
+                group_id: None,
+            });
+        }
+        for profile in &mut profiles {
+            profile.interval.get_or_insert(self.interval);
+            profile.filter.get_or_insert_with(|| self.filter.clone());
+            profile.group_id.get_or_insert(self.telegram.group_id);
+        }
+        profiles
+    }
+
+    /// 默认的搜索配置
+    pub fn default_profile(&self) -> Profile {
+        self.profiles().swap_remove(0)
+    }
+
+    /// 根据频道或者讨论组找到对应的搜索配置，找不到时返回默认配置
+    pub fn profile_for_chat(&self, chat: &Chat) -> Profile {
+        let mut profiles = self.profiles();
+        let index = profiles.iter().position(|p| p.contains(chat)).unwrap_or(0);
+        profiles.swap_remove(index)
+    }
+
+    /// 根据数据库中记录的频道找到对应的搜索配置
+    pub fn profile_for_channel(&self, channel: &str) -> Option<Profile> {
+        self.profiles().into_iter().find(|p| p.channel_key() == channel)
+    }
+}
+
+impl ExHentai {
+    /// 所有账号，cookie 中填写的账号排在最前面
+    pub fn accounts(&self) -> Vec<Account> {
+        let mut accounts = vec![];
+        if !self.cookie.is_empty() {
+            accounts.push(Account {
+                name: "default".to_string(),
+                cookie: self.cookie.clone(),
+                proxy: None,
+            });
+        }
+        accounts.extend(self.accounts.iter().cloned());
+        accounts
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Account {
+    /// 账号名称，用于日志和通知
+    pub name: String,
     /// 登陆 cookie
     pub cookie: String,
+    /// 该账号使用的代理，未设置时使用全局的 proxy
+    pub proxy: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Profile {
+    /// 配置名称，用于区分扫描进度，修改后会重新开始扫描
+    pub name: String,
     /// 搜索参数
     pub search_params: Vec<(String, String)>,
     /// 最大遍历画廊数量
     pub search_count: usize,
-    /// 翻译文件的位置
-    pub trans_file: String,
+    /// 扫描间隔，未设置时使用全局的 interval
+    #[serde(default, deserialize_with = "deserialize_option_duration")]
+    pub interval: Option<Duration>,
+    /// 过滤规则，未设置时使用全局的 [filter]
+    pub filter: Option<Filter>,
+    /// 发布画廊的频道
+    pub channel_id: Recipient,
+    /// 频道的讨论组，用于发送投票，未设置时使用 [telegram] 中的 group_id
+    pub group_id: Option<ChatId>,
+}
+
+impl Profile {
+    /// 数据库中记录频道时使用的字符串
+    pub fn channel_key(&self) -> String {
+        self.channel_id.to_string()
+    }
+
+    /// 指定的聊天是否为该配置的频道
+    pub fn is_channel(&self, chat: &Chat) -> bool {
+        match &self.channel_id {
+            Recipient::Id(id) => *id == chat.id,
+            Recipient::ChannelUsername(name) => chat.username() == Some(&name[1..]),
+        }
+    }
+
+    /// 指定的聊天是否为该配置的频道或者讨论组
+    pub fn contains(&self, chat: &Chat) -> bool {
+        self.is_channel(chat) || self.group_id == Some(chat.id)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -74,6 +237,15 @@ pub struct Telegram {
     pub allow_public_commands: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageHostKind {
+    /// teletype.in
+    Teletype,
+    /// S3 兼容的对象存储
+    S3,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct S3 {
     /// region
@@ -96,6 +268,158 @@ pub struct Teletype {
     pub token: String,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Transcode {
+    /// 超过该大小（字节）的图片会在上传前重新编码
+    pub max_size: usize,
+    /// 重新编码时图片的最大边长，超过时会等比缩放
+    pub max_dimension: u32,
+    /// 重新编码的格式
+    pub format: TranscodeFormat,
+    /// JPEG 编码质量（1~100），WebP 始终使用无损编码
+    pub quality: u8,
+}
+
+impl Default for Transcode {
+    fn default() -> Self {
+        Self { max_size: 2_000_000, max_dimension: 2560, format: TranscodeFormat::Jpeg, quality: 90 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscodeFormat {
+    Jpeg,
+    WebP,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Retry {
+    /// 连续失败多少次后暂停自动上传，直到管理员手动解除
+    pub max_failures: u32,
+    /// 第一次失败后的重试间隔，之后每次失败翻倍
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub base_delay: Duration,
+    /// 重试间隔的上限
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub max_delay: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            max_failures: 5,
+            base_delay: Duration::from_secs(60 * 60),
+            max_delay: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+impl Retry {
+    /// 第 attempts 次失败后，距离下次重试的间隔
+    pub fn delay(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PartialPublish {
+    /// 处理方式
+    pub policy: MissingPagePolicy,
+    /// 最多容忍多少页缺失，超过时仍然跳过整个画廊
+    pub max_missing: usize,
+    /// 占位图片的 URL，仅在 policy = "placeholder" 时使用
+    pub placeholder_url: Option<String>,
+}
+
+impl Default for PartialPublish {
+    fn default() -> Self {
+        Self { policy: MissingPagePolicy::Skip, max_missing: 5, placeholder_url: None }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MissingPagePolicy {
+    /// 跳过整个画廊
+    Skip,
+    /// 使用占位图片代替缺失的页面
+    Placeholder,
+    /// 先发布已有的页面，缺失的页面在 recheck 时补上
+    Defer,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Filter {
+    /// 标签黑名单，按 namespace 分组，含有其中任意一个标签的画廊不会上传
+    pub tag_blacklist: HashMap<String, Vec<String>>,
+    /// 标签白名单，按 namespace 分组，画廊在列出的每个 namespace 下都至少要含有一个标签
+    pub tag_whitelist: HashMap<String, Vec<String>>,
+    /// 最少页数
+    pub min_pages: Option<usize>,
+    /// 最多页数
+    pub max_pages: Option<usize>,
+    /// 最少收藏数
+    pub min_favorite: Option<i32>,
+    /// 最低平均评分
+    pub min_rating: Option<f32>,
+    /// 只上传这些分类的画廊，为空时不限制，不区分大小写
+    pub categories: Vec<String>,
+    /// 只上传这些语言的画廊，为空时不限制，不区分大小写
+    pub languages: Vec<String>,
+    /// 只上传发布时间在此之内的画廊
+    #[serde(deserialize_with = "deserialize_option_duration")]
+    pub max_age: Option<Duration>,
+    /// 标题黑名单，正则表达式，同时匹配英文和日文标题
+    pub title_blacklist: Vec<String>,
+    /// 标题白名单，正则表达式，设置后标题至少要匹配其中一个
+    pub title_whitelist: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AdImage {
+    /// 是否将含有二维码的图片标记为广告
+    pub detect_qrcode: bool,
+    /// 同一张图片出现在超过这么多个画廊中时标记为广告
+    pub max_galleries: i32,
+}
+
+impl Default for AdImage {
+    fn default() -> Self {
+        Self { detect_qrcode: true, max_galleries: 5 }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Quota {
+    /// 是否在自动上传前检查图片额度
+    pub enabled: bool,
+    /// 额外保留的额度，剩余额度减去这个值之后仍然不够时，推迟上传
+    pub reserve: u32,
+}
+
+impl Default for Quota {
+    fn default() -> Self {
+        Self { enabled: true, reserve: 0 }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Comment {
+    /// 在投票下方发送得分最高的多少条评论，为 0 时不发送
+    pub post_top: i32,
+    /// 只发送得分不低于该值的评论
+    pub min_score: i32,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Backup {
     /// 是否启用定时备份
@@ -117,6 +441,58 @@ pub struct Backup {
 impl Config {
     pub fn new(path: &str) -> Result<Self> {
         let s = std::fs::read_to_string(path)?;
-        Ok(toml::from_str(&s)?)
+        let config: Self = toml::from_str(&s)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 检查无法通过反序列化发现的配置错误
+    fn validate(&self) -> Result<()> {
+        let partial = &self.partial_publish;
+        if partial.policy == MissingPagePolicy::Placeholder && partial.placeholder_url.is_none() {
+            bail!("partial_publish.policy 为 placeholder 时必须设置 placeholder_url");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_profiles() {
+        let mut config = Config::new("config.toml.example").unwrap();
+        let profiles = config.profiles();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].name, "default");
+        assert_eq!(profiles[0].channel_key(), "@xxx");
+        assert_eq!(profiles[0].interval, Some(config.interval));
+
+        let profile: Profile = toml::from_str(
+            r#"
+            name = "other"
+            search_params = []
+            search_count = 5
+            channel_id = -1001234
+            interval = "5m"
+            "#,
+        )
+        .unwrap();
+        config.profiles = vec![config.default_profile(), profile];
+        assert_eq!(config.profile_for_channel("-1001234").unwrap().name, "other");
+        assert_eq!(config.profiles()[1].interval, Some(Duration::from_secs(300)));
+        assert_eq!(config.profiles()[1].group_id, Some(config.telegram.group_id));
+        assert!(config.profile_for_channel("@yyy").is_none());
+    }
+
+    #[test]
+    fn placeholder_requires_url() {
+        let mut config = Config::new("config.toml.example").unwrap();
+        config.partial_publish.policy = MissingPagePolicy::Placeholder;
+        config.partial_publish.placeholder_url = None;
+        assert!(config.validate().is_err());
+        config.partial_publish.placeholder_url = Some("https://example.com/404.png".into());
+        assert!(config.validate().is_ok());
     }
 }
@@ -2,7 +2,6 @@ use anyhow::Result;
 use tokio::process::Command;
 use tokio::time::{sleep, Duration};
 use tracing::{error, info, warn};
-use std::env;
 
 pub async fn start_daemon(app_path: &str) -> Result<()> {
     info!("守护进程启动，监控应用程序: {}", app_path);
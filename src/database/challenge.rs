@@ -44,11 +44,10 @@ impl ChallengeView {
                 SELECT * FROM (
                     SELECT * FROM challenge_view
                     WHERE score > 0.8 AND image_id NOT IN (
-                        -- 此处过滤掉出现在大于 5 个画廊中的图片，因为大概率是广告
+                        -- 此处过滤掉被标记过的图片（广告、无效图片等）
                         -- 还有第一页和最后一页
-                        -- 这个查询太耗时了，现在有基于二维码的过滤了，暂时禁用看一下效果
-                        -- SELECT image_id FROM page GROUP BY image_id HAVING COUNT(gallery_id) > 5
-                        -- UNION
+                        SELECT image.id FROM image_flag JOIN image ON image.hash = image_flag.hash
+                        UNION
                         SELECT image_id FROM page GROUP BY gallery_id HAVING page = MAX(page)
                         UNION
                         SELECT image_id FROM page GROUP BY gallery_id HAVING page = 1
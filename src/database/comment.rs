@@ -0,0 +1,71 @@
+use chrono::NaiveDateTime;
+use sqlx::Result;
+use tracing::Level;
+
+use super::db::DB;
+use crate::ehentai::EhComment;
+
+/// E 站上的画廊评论
+#[derive(sqlx::FromRow, Debug)]
+pub struct CommentEntity {
+    /// 画廊 ID
+    pub gallery_id: i32,
+    /// 评论 ID，上传者的评论为 0
+    pub comment_id: i64,
+    /// 评论者
+    pub author: String,
+    /// 评论得分
+    pub score: i32,
+    /// 评论内容
+    pub content: String,
+    /// 评论时间
+    pub posted: NaiveDateTime,
+}
+
+impl CommentEntity {
+    /// 使用最新获取的评论替换画廊的所有评论，E 站上被删除的评论也会一起删除
+    #[tracing::instrument(level = Level::DEBUG, skip(comments))]
+    pub async fn replace(gallery_id: i32, comments: &[EhComment]) -> Result<()> {
+        let mut tx = DB.begin().await?;
+        sqlx::query!("DELETE FROM comment WHERE gallery_id = ?", gallery_id)
+            .execute(&mut *tx)
+            .await?;
+        for comment in comments {
+            sqlx::query!(
+                "INSERT OR REPLACE INTO comment (gallery_id, comment_id, author, score, content, posted) VALUES (?, ?, ?, ?, ?, ?)",
+                gallery_id,
+                comment.id,
+                comment.author,
+                comment.score,
+                comment.content,
+                comment.posted,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    /// 获取画廊得分最高的评论，不包括上传者的评论
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn list_top(gallery_id: i32, min_score: i32, limit: i32) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                gallery_id as "gallery_id: i32",
+                comment_id,
+                author,
+                score as "score: i32",
+                content,
+                posted
+            FROM comment
+            WHERE gallery_id = ? AND comment_id != 0 AND score >= ?
+            ORDER BY score DESC LIMIT ?"#,
+            gallery_id,
+            min_score,
+            limit
+        )
+        .fetch_all(&*DB)
+        .await
+    }
+}
@@ -11,8 +11,7 @@ use sqlx::{Database, Result, Sqlite};
 use tracing::Level;
 
 use super::db::DB;
-use crate::config::CHANNEL_ID;
-use crate::ehentai::EhGallery;
+use crate::ehentai::{EhGallery, EhGalleryMeta};
 
 // 此处使用 IndexMap，因为我们需要保证相同的 tag 每次序列化的结果都是一样的
 #[derive(Debug, Clone, Default)]
@@ -41,10 +40,24 @@ pub struct GalleryEntity {
     pub deleted: bool,
     /// 发布时间
     pub posted: Option<NaiveDateTime>,
+    /// 分类，旧画廊可能为空，下同
+    pub category: Option<String>,
+    /// 语言
+    pub language: Option<String>,
+    /// 上传者
+    pub uploader: Option<String>,
+    /// 所有图片的总大小，单位为字节
+    pub filesize: Option<i64>,
+    /// 平均评分
+    pub rating: Option<f32>,
+    /// 评分人数
+    pub rating_count: Option<i32>,
+    /// 下次检查更新的时间，为空时表示尽快检查
+    pub next_check_at: Option<NaiveDateTime>,
 }
 
 impl GalleryEntity {
-    /// 创建一条记录
+    /// 创建一条记录，已存在时只更新画廊本身的信息，下次检查时间等其他字段保持不变
     #[tracing::instrument(level = Level::DEBUG)]
     pub async fn create(g: &EhGallery) -> Result<SqliteQueryResult> {
         let id = g.url.id();
@@ -52,8 +65,14 @@ impl GalleryEntity {
         let tags = serde_json::to_string(&g.tags).unwrap();
         let pages = g.pages.len() as i32;
         let parent = g.parent.as_ref().map(|g| g.id());
+        let filesize = g.filesize as i64;
         sqlx::query!(
-            "REPLACE INTO gallery (id, token, title, title_jp, tags, favorite, pages, parent, deleted, posted) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            r#"INSERT INTO gallery (id, token, title, title_jp, tags, favorite, pages, parent, deleted, posted, category, language, uploader, filesize, rating, rating_count)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (id) DO UPDATE SET
+                token = excluded.token, title = excluded.title, title_jp = excluded.title_jp, tags = excluded.tags, favorite = excluded.favorite,
+                pages = excluded.pages, parent = excluded.parent, deleted = excluded.deleted, posted = excluded.posted, category = excluded.category,
+                language = excluded.language, uploader = excluded.uploader, filesize = excluded.filesize, rating = excluded.rating, rating_count = excluded.rating_count"#,
             id,
             token,
             g.title,
@@ -64,11 +83,47 @@ impl GalleryEntity {
             parent,
             false,
             g.posted,
+            g.category,
+            g.language,
+            g.uploader,
+            filesize,
+            g.rating,
+            g.rating_count,
         )
             .execute(&*DB)
             .await
     }
 
+    /// 使用 API 返回的元数据创建一条记录，已存在时忽略，用于补充没有上传过的祖先画廊
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn create_from_meta(meta: &EhGalleryMeta) -> Result<SqliteQueryResult> {
+        let tags = serde_json::to_string(&meta.tags()).unwrap();
+        let title_jp = meta.title_jp();
+        let pages = meta.filecount as i32;
+        let parent = meta.parent().map(|g| g.id());
+        let posted = meta.posted();
+        let filesize = meta.filesize as i64;
+        let language = meta.language();
+        sqlx::query!(
+            "INSERT OR IGNORE INTO gallery (id, token, title, title_jp, tags, pages, parent, deleted, posted, category, language, uploader, filesize, rating) VALUES (?, ?, ?, ?, ?, ?, ?, FALSE, ?, ?, ?, ?, ?, ?)",
+            meta.gid,
+            meta.token,
+            meta.title,
+            title_jp,
+            tags,
+            pages,
+            parent,
+            posted,
+            meta.category,
+            language,
+            meta.uploader,
+            filesize,
+            meta.rating,
+        )
+        .execute(&*DB)
+        .await
+    }
+
     /// 根据 ID 获取一条记录
     ///
     /// 注意，此处不会返回已被标记为删除的记录
@@ -80,12 +135,18 @@ impl GalleryEntity {
             .await
     }
 
-    /// 根据消息 ID 获取一条记录
-    pub async fn get_by_msg(id: i32) -> Result<Option<GalleryEntity>> {
+    /// 根据 ID 获取一条记录，包括已被标记为删除的记录
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn get_with_deleted(id: i32) -> Result<Option<GalleryEntity>> {
+        sqlx::query_as("SELECT * FROM gallery WHERE id = ?").bind(id).fetch_optional(&*DB).await
+    }
+
+    /// 根据频道和消息 ID 获取一条记录
+    pub async fn get_by_msg(channel_id: &str, id: i32) -> Result<Option<GalleryEntity>> {
         sqlx::query_as(
             "SELECT gallery.* FROM gallery JOIN message ON gallery.id = message.gallery_id AND message.channel_id = ? WHERE message.id = ? AND gallery.deleted = FALSE"
         )
-            .bind(CHANNEL_ID.get().unwrap())
+            .bind(channel_id)
             .bind(id)
             .fetch_optional(&*DB)
             .await
@@ -100,6 +161,33 @@ impl GalleryEntity {
             .map(|x| x == Some(1))
     }
 
+    /// 使用 API 返回的元数据更新标题、标签、发布时间、分类、语言、上传者、大小和评分
+    ///
+    /// 评分人数不在 API 中，由 update_rating_count 单独更新；标签中没有语言时保留原来的值
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn update_meta(meta: &EhGalleryMeta) -> Result<SqliteQueryResult> {
+        let tags = serde_json::to_string(&meta.tags()).unwrap();
+        let title_jp = meta.title_jp();
+        let posted = meta.posted();
+        let filesize = meta.filesize as i64;
+        let language = meta.language();
+        sqlx::query!(
+            "UPDATE gallery SET title = ?, title_jp = ?, tags = ?, posted = ?, category = ?, language = COALESCE(?, language), uploader = ?, filesize = ?, rating = ? WHERE id = ?",
+            meta.title,
+            title_jp,
+            tags,
+            posted,
+            meta.category,
+            language,
+            meta.uploader,
+            filesize,
+            meta.rating,
+            meta.gid,
+        )
+        .execute(&*DB)
+        .await
+    }
+
     /// 根据 ID 更新 tag
     #[tracing::instrument(level = Level::DEBUG)]
     pub async fn update_tags(id: i32, tags: &[(String, Vec<String>)]) -> Result<SqliteQueryResult> {
@@ -107,6 +195,37 @@ impl GalleryEntity {
         sqlx::query!("UPDATE gallery SET tags = ? WHERE id = ?", tags, id).execute(&*DB).await
     }
 
+    /// 更新评分人数，API 中没有这一项，只能从画廊页面获取
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn update_rating_count(id: i32, rating_count: i32) -> Result<SqliteQueryResult> {
+        sqlx::query!("UPDATE gallery SET rating_count = ? WHERE id = ?", rating_count, id)
+            .execute(&*DB)
+            .await
+    }
+
+    /// 设置下次检查更新的时间
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn update_next_check(id: i32, at: NaiveDateTime) -> Result<SqliteQueryResult> {
+        sqlx::query!("UPDATE gallery SET next_check_at = ? WHERE id = ?", at, id).execute(&*DB).await
+    }
+
+    /// 列出已经到了检查更新时间的画廊，只包括发布过消息并且没有被删除的画廊
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn list_due(limit: i32) -> Result<Vec<Self>> {
+        let now = Utc::now().naive_utc();
+        sqlx::query_as(
+            r#"SELECT * FROM gallery
+            WHERE deleted = FALSE
+                AND (next_check_at IS NULL OR next_check_at <= ?)
+                AND EXISTS (SELECT 1 FROM message WHERE message.gallery_id = gallery.id)
+            ORDER BY next_check_at LIMIT ?"#,
+        )
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&*DB)
+        .await
+    }
+
     /// 根据 ID 更新删除状态
     #[tracing::instrument(level = Level::DEBUG)]
     pub async fn update_deleted(id: i32, deleted: bool) -> Result<SqliteQueryResult> {
@@ -119,10 +238,11 @@ impl GalleryEntity {
         sqlx::query!("DELETE FROM gallery WHERE id = ?", id).execute(&*DB).await
     }
 
-    /// 查询自指定日期以来的本子，结果按分数从高到低排列
+    /// 查询指定频道自指定日期以来的本子，结果按分数从高到低排列
     /// 返回 分数、标题、画廊 ID
     #[tracing::instrument(level = Level::DEBUG)]
     pub async fn list(
+        channel_id: &str,
         start: NaiveDate,
         end: NaiveDate,
         limit: i32,
@@ -132,11 +252,13 @@ impl GalleryEntity {
         let record = sqlx::query!(
             r#"SELECT poll.score, gallery.title, gallery.id
             FROM gallery
-            JOIN poll ON poll.gallery_id = gallery.id
-            JOIN message ON message.gallery_id = gallery.id
+            JOIN poll ON poll.gallery_id = gallery.id AND poll.channel_id = ?
+            JOIN message ON message.gallery_id = gallery.id AND message.channel_id = ?
             WHERE gallery.posted BETWEEN ? AND ?
             GROUP BY poll.id
             ORDER BY poll.score DESC LIMIT ? OFFSET ?"#,
+            channel_id,
+            channel_id,
             start,
             end,
             limit,
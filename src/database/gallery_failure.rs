@@ -0,0 +1,137 @@
+use chrono::{NaiveDateTime, Utc};
+use sqlx::sqlite::SqliteQueryResult;
+use sqlx::Result;
+use tracing::Level;
+
+use super::db::DB;
+use crate::ehentai::EhGalleryUrl;
+
+/// 自动上传失败的画廊
+#[derive(sqlx::FromRow, Debug)]
+pub struct GalleryFailureEntity {
+    /// 画廊 ID
+    pub gallery_id: i32,
+    /// 画廊 token
+    pub token: String,
+    /// 负责重试的搜索配置
+    pub profile: String,
+    /// 连续失败次数
+    pub attempts: i32,
+    /// 最后一次失败的原因
+    pub last_error: String,
+    /// 在此之前不会再自动尝试上传
+    pub next_retry_at: NaiveDateTime,
+    /// 是否已暂停自动上传，需要管理员手动解除
+    pub parked: bool,
+    pub updated_at: NaiveDateTime,
+}
+
+impl GalleryFailureEntity {
+    /// 创建或覆盖一条记录
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn create(
+        gallery: &EhGalleryUrl,
+        profile: &str,
+        attempts: i32,
+        last_error: &str,
+        next_retry_at: NaiveDateTime,
+        parked: bool,
+    ) -> Result<SqliteQueryResult> {
+        let id = gallery.id();
+        let token = gallery.token();
+        let now = Utc::now().naive_utc();
+        sqlx::query!(
+            "REPLACE INTO gallery_failure (gallery_id, token, profile, attempts, last_error, next_retry_at, parked, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            id,
+            token,
+            profile,
+            attempts,
+            last_error,
+            next_retry_at,
+            parked,
+            now,
+        )
+        .execute(&*DB)
+        .await
+    }
+
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn get(gallery_id: i32) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                gallery_id as "gallery_id: i32",
+                token,
+                profile,
+                attempts as "attempts: i32",
+                last_error,
+                next_retry_at,
+                parked,
+                updated_at
+            FROM gallery_failure WHERE gallery_id = ?"#,
+            gallery_id
+        )
+        .fetch_optional(&*DB)
+        .await
+    }
+
+    /// 获取所有已暂停自动上传的画廊
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn list_parked() -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                gallery_id as "gallery_id: i32",
+                token,
+                profile,
+                attempts as "attempts: i32",
+                last_error,
+                next_retry_at,
+                parked,
+                updated_at
+            FROM gallery_failure WHERE parked ORDER BY updated_at DESC"#
+        )
+        .fetch_all(&*DB)
+        .await
+    }
+
+    /// 获取指定搜索配置中所有已到重试时间的画廊
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn list_due(profile: &str) -> Result<Vec<Self>> {
+        let now = Utc::now().naive_utc();
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                gallery_id as "gallery_id: i32",
+                token,
+                profile,
+                attempts as "attempts: i32",
+                last_error,
+                next_retry_at,
+                parked,
+                updated_at
+            FROM gallery_failure WHERE profile = ? AND NOT parked AND next_retry_at <= ? ORDER BY gallery_id DESC"#,
+            profile,
+            now
+        )
+        .fetch_all(&*DB)
+        .await
+    }
+
+    /// 删除记录，上传成功或者管理员手动解除时调用
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn delete(gallery_id: i32) -> Result<SqliteQueryResult> {
+        sqlx::query!("DELETE FROM gallery_failure WHERE gallery_id = ?", gallery_id)
+            .execute(&*DB)
+            .await
+    }
+
+    /// 下次可以自动上传的时间是否已到
+    pub fn is_due(&self) -> bool {
+        !self.parked && self.next_retry_at <= Utc::now().naive_utc()
+    }
+
+    pub fn url(&self) -> EhGalleryUrl {
+        format!("https://exhentai.org/g/{}/{}", self.gallery_id, self.token).parse().unwrap()
+    }
+}
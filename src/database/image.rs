@@ -24,6 +24,16 @@ pub struct ImageEntity {
     url: String,
 }
 
+#[derive(sqlx::FromRow, Debug)]
+pub struct ImageMirrorEntity {
+    /// 图片在 E 站的 fileindex
+    pub image_id: u32,
+    /// 图床名称
+    pub host: String,
+    /// 图片在该图床上的 URL
+    pub url: String,
+}
+
 impl ImageEntity {
     /// 创建一条记录
     #[tracing::instrument(level = Level::DEBUG)]
@@ -66,6 +76,12 @@ impl ImageEntity {
         .await
     }
 
+    /// 更新图片的主 URL，用于在图床失效时切换到镜像
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn update_url(id: u32, url: &str) -> Result<SqliteQueryResult> {
+        sqlx::query!("UPDATE image SET url = ? WHERE id = ?", url, id).execute(&*DB).await
+    }
+
     pub fn url(&self) -> String {
         if self.url.starts_with("/file/") {
             format!("https://telegra.ph{}", self.url)
@@ -75,6 +91,41 @@ impl ImageEntity {
     }
 }
 
+impl ImageMirrorEntity {
+    /// 创建一条记录，如果该图床已有记录则覆盖
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn create(image_id: u32, host: &str, url: &str) -> Result<SqliteQueryResult> {
+        sqlx::query!(
+            "REPLACE INTO image_mirror (image_id, host, url) VALUES (?, ?, ?)",
+            image_id,
+            host,
+            url
+        )
+        .execute(&*DB)
+        .await
+    }
+
+    /// 获取指定画廊所有图片的全部镜像
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn get_by_gallery_id(gallery_id: i32) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                image_mirror.image_id as "image_id: u32",
+                image_mirror.host,
+                image_mirror.url
+            FROM image_mirror
+            JOIN page ON page.image_id = image_mirror.image_id
+            WHERE page.gallery_id = ?
+            "#,
+            gallery_id,
+        )
+        .fetch_all(&*DB)
+        .await
+    }
+}
+
 impl PageEntity {
     /// 创建一条记录，有冲突时则忽略
     #[tracing::instrument(level = Level::DEBUG)]
@@ -89,6 +140,49 @@ impl PageEntity {
         .await
     }
 
+    /// 统计除指定画廊外，还有多少个画廊使用了这张图片
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn count_other_galleries(image_id: u32, gallery_id: i32) -> Result<i32> {
+        sqlx::query_scalar!(
+            "SELECT COUNT(DISTINCT gallery_id) FROM page WHERE image_id = ? AND gallery_id != ?",
+            image_id,
+            gallery_id
+        )
+        .fetch_one(&*DB)
+        .await
+    }
+
+    /// 获取指定画廊已记录的页码和对应图片的 hash
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn list_hashes(gallery_id: i32) -> Result<Vec<(i32, String)>> {
+        let rows = sqlx::query!(
+            r#"SELECT page.page as "page: i32", image.hash FROM page JOIN image ON page.image_id = image.id WHERE page.gallery_id = ?"#,
+            gallery_id
+        )
+        .fetch_all(&*DB)
+        .await?;
+        Ok(rows.into_iter().map(|r| (r.page, r.hash)).collect())
+    }
+
+    /// 获取指定画廊已记录的页码，从小到大排列，与 ImageEntity::get_by_gallery_id 的顺序一致
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn list_pages(gallery_id: i32) -> Result<Vec<i32>> {
+        sqlx::query_scalar!(
+            r#"SELECT page as "page: i32" FROM page WHERE gallery_id = ? ORDER BY page"#,
+            gallery_id
+        )
+        .fetch_all(&*DB)
+        .await
+    }
+
+    /// 删除指定画廊中某一页的记录
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn delete(gallery_id: i32, page: i32) -> Result<SqliteQueryResult> {
+        sqlx::query!("DELETE FROM page WHERE gallery_id = ? AND page = ?", gallery_id, page)
+            .execute(&*DB)
+            .await
+    }
+
     /// 统计某个画廊的有记录页面数量
     pub async fn count(gallery_id: i32) -> Result<i32> {
         sqlx::query_scalar!("SELECT COUNT(*) FROM page WHERE gallery_id = ?", gallery_id)
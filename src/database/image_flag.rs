@@ -0,0 +1,85 @@
+use chrono::{NaiveDateTime, Utc};
+use sqlx::sqlite::SqliteQueryResult;
+use sqlx::Result;
+use tracing::Level;
+
+use super::db::DB;
+
+/// 图片标记的类型
+#[derive(sqlx::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "lowercase")]
+pub enum ImageFlagKind {
+    /// 广告，上传时会被跳过
+    Ad,
+    /// 无效图片，比如损坏或者内容不正确，不会出现在挑战中
+    Invalid,
+}
+
+/// 被标记的图片，以图片 hash 为准，因此同一张图片在所有画廊中都会生效
+#[derive(sqlx::FromRow, Debug)]
+pub struct ImageFlagEntity {
+    /// 图片的 sha1sum 前 10 位
+    pub hash: String,
+    /// 标记类型
+    pub kind: ImageFlagKind,
+    /// 标记原因
+    pub reason: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl ImageFlagEntity {
+    /// 标记一张图片，已经标记过时覆盖
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn create(
+        hash: &str,
+        kind: ImageFlagKind,
+        reason: &str,
+    ) -> Result<SqliteQueryResult> {
+        let now = Utc::now().naive_utc();
+        sqlx::query!(
+            "REPLACE INTO image_flag (hash, kind, reason, created_at) VALUES (?, ?, ?, ?)",
+            hash,
+            kind,
+            reason,
+            now,
+        )
+        .execute(&*DB)
+        .await
+    }
+
+    /// 根据图片 id 标记一张图片
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn create_by_image_id(
+        image_id: i32,
+        kind: ImageFlagKind,
+        reason: &str,
+    ) -> Result<SqliteQueryResult> {
+        let now = Utc::now().naive_utc();
+        sqlx::query!(
+            "REPLACE INTO image_flag (hash, kind, reason, created_at) SELECT hash, ?, ?, ? FROM image WHERE id = ?",
+            kind,
+            reason,
+            now,
+            image_id,
+        )
+        .execute(&*DB)
+        .await
+    }
+
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn get(hash: &str) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT hash, kind as "kind: ImageFlagKind", reason, created_at FROM image_flag WHERE hash = ?"#,
+            hash
+        )
+        .fetch_optional(&*DB)
+        .await
+    }
+
+    /// 取消标记
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn delete(hash: &str) -> Result<SqliteQueryResult> {
+        sqlx::query!("DELETE FROM image_flag WHERE hash = ?", hash).execute(&*DB).await
+    }
+}
@@ -1,10 +1,10 @@
 use chrono::{NaiveDate, Utc};
 use sqlx::sqlite::SqliteQueryResult;
 use sqlx::Result;
+use teloxide::types::{ChatId, Recipient};
 use tracing::Level;
 
 use super::db::DB;
-use crate::config::CHANNEL_ID;
 
 #[derive(sqlx::FromRow, Debug)]
 pub struct MessageEntity {
@@ -20,8 +20,7 @@ pub struct MessageEntity {
 
 impl MessageEntity {
     #[tracing::instrument(level = Level::DEBUG)]
-    pub async fn create(id: i32, gid: i32) -> Result<SqliteQueryResult> {
-        let channel_id = CHANNEL_ID.get().unwrap();
+    pub async fn create(channel_id: &str, id: i32, gid: i32) -> Result<SqliteQueryResult> {
         let now = Utc::now().date_naive();
         sqlx::query!(
             "INSERT INTO message (id, channel_id, gallery_id, publish_date) VALUES (?, ?, ?, ?)",
@@ -36,8 +35,7 @@ impl MessageEntity {
 
     // TODO: 如果存在与否不重要，其实不需要返回 Option，否则反而不方便上抛错误
     #[tracing::instrument(level = Level::DEBUG)]
-    pub async fn get(id: i32) -> Result<Option<MessageEntity>> {
-        let channel_id = CHANNEL_ID.get().unwrap();
+    pub async fn get(channel_id: &str, id: i32) -> Result<Option<MessageEntity>> {
         sqlx::query_as!(
             MessageEntity,
             r#"
@@ -56,16 +54,14 @@ impl MessageEntity {
     }
 
     #[tracing::instrument(level = Level::DEBUG)]
-    pub async fn delete(id: i32) -> Result<SqliteQueryResult> {
-        let channel_id = CHANNEL_ID.get().unwrap();
+    pub async fn delete(channel_id: &str, id: i32) -> Result<SqliteQueryResult> {
         sqlx::query!("DELETE FROM message WHERE id = ? AND channel_id = ?", id, channel_id)
             .execute(&*DB)
             .await
     }
 
     #[tracing::instrument(level = Level::DEBUG)]
-    pub async fn get_by_gallery(gid: i32) -> Result<Option<MessageEntity>> {
-        let channel_id = CHANNEL_ID.get().unwrap();
+    pub async fn get_by_gallery(channel_id: &str, gid: i32) -> Result<Option<MessageEntity>> {
         sqlx::query_as!(
             MessageEntity,
             r#"
@@ -85,10 +81,38 @@ impl MessageEntity {
         .await
     }
 
+    /// 获取画廊在所有频道中的消息，按发布日期排列
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn list_by_gallery(gid: i32) -> Result<Vec<MessageEntity>> {
+        sqlx::query_as!(
+            MessageEntity,
+            r#"
+            SELECT
+                id as "id: i32",
+                channel_id,
+                gallery_id as "gallery_id: i32",
+                publish_date
+            FROM message
+            WHERE gallery_id = ?
+            ORDER BY publish_date
+            "#,
+            gid,
+        )
+        .fetch_all(&*DB)
+        .await
+    }
+
+    /// 消息所在的频道
+    pub fn chat(&self) -> Recipient {
+        match self.channel_id.parse() {
+            Ok(id) => Recipient::Id(ChatId(id)),
+            Err(_) => Recipient::ChannelUsername(self.channel_id.clone()),
+        }
+    }
+
     /// 根据画廊ID删除消息记录
     #[tracing::instrument(level = Level::DEBUG)]
-    pub async fn delete_by_gallery(gallery_id: i32) -> Result<SqliteQueryResult> {
-        let channel_id = CHANNEL_ID.get().unwrap();
+    pub async fn delete_by_gallery(channel_id: &str, gallery_id: i32) -> Result<SqliteQueryResult> {
         sqlx::query("DELETE FROM message WHERE gallery_id = ? AND channel_id = ?")
             .bind(gallery_id)
             .bind(channel_id)
@@ -0,0 +1,76 @@
+use chrono::{NaiveDateTime, Utc};
+use sqlx::sqlite::SqliteQueryResult;
+use sqlx::Result;
+use tracing::Level;
+
+use super::db::DB;
+use crate::ehentai::EhPageUrl;
+
+/// 上传时无法下载、在文章中暂时缺失的页面
+#[derive(sqlx::FromRow, Debug)]
+pub struct MissingPageEntity {
+    /// 画廊 ID
+    pub gallery_id: i32,
+    /// 页面编号
+    pub page: i32,
+    /// 图片的 sha1sum 前 10 位
+    pub hash: String,
+    /// 下载失败的原因
+    pub error: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl MissingPageEntity {
+    /// 创建一条记录，已存在时覆盖
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn create(page: &EhPageUrl, error: &str) -> Result<SqliteQueryResult> {
+        let gallery_id = page.gallery_id();
+        let page_num = page.page();
+        let hash = page.hash();
+        let now = Utc::now().naive_utc();
+        sqlx::query!(
+            "REPLACE INTO missing_page (gallery_id, page, hash, error, created_at) VALUES (?, ?, ?, ?, ?)",
+            gallery_id,
+            page_num,
+            hash,
+            error,
+            now,
+        )
+        .execute(&*DB)
+        .await
+    }
+
+    /// 获取指定画廊缺失的页面，按页码排列
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn get_by_gallery(gallery_id: i32) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                gallery_id as "gallery_id: i32",
+                page as "page: i32",
+                hash,
+                error,
+                created_at
+            FROM missing_page WHERE gallery_id = ? ORDER BY page"#,
+            gallery_id
+        )
+        .fetch_all(&*DB)
+        .await
+    }
+
+    /// 获取所有存在缺失页面的画廊 ID
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn list_galleries() -> Result<Vec<i32>> {
+        sqlx::query_scalar!(r#"SELECT DISTINCT gallery_id as "gallery_id: i32" FROM missing_page"#)
+            .fetch_all(&*DB)
+            .await
+    }
+
+    /// 删除指定画廊的所有缺失记录
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn delete_by_gallery(gallery_id: i32) -> Result<SqliteQueryResult> {
+        sqlx::query!("DELETE FROM missing_page WHERE gallery_id = ?", gallery_id)
+            .execute(&*DB)
+            .await
+    }
+}
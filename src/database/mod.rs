@@ -1,16 +1,28 @@
 mod challenge;
+mod comment;
 mod db;
 mod gallery;
+mod gallery_failure;
 mod image;
+mod image_flag;
 mod invite_link;
 mod message;
+mod missing_page;
 mod poll;
+mod scan_state;
 mod telegraph;
+mod upload_job;
 
 pub use challenge::*;
+pub use comment::*;
 pub use gallery::*;
+pub use gallery_failure::*;
 pub use image::*;
+pub use image_flag::*;
 pub use invite_link::*;
 pub use message::*;
+pub use missing_page::*;
 pub use poll::*;
+pub use scan_state::*;
 pub use telegraph::*;
+pub use upload_job::*;
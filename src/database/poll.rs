@@ -12,6 +12,8 @@ pub struct PollEntity {
     pub id: i64,
     /// 画廊 ID
     pub gallery_id: i32,
+    /// 投票所属的频道，同一个画廊在不同频道中的投票互相独立
+    pub channel_id: Option<String>,
     /// 当前投票的分数，为 0~1 的小数
     pub score: f32,
     /// 旧系统的投票数据，代表 1~5 的投票数量
@@ -33,27 +35,58 @@ pub struct VoteEntity {
 impl PollEntity {
     /// 插入一条记录，如果冲突则忽略
     #[tracing::instrument(level = Level::DEBUG)]
-    pub async fn create(id: i64, gallery_id: i32) -> Result<SqliteQueryResult> {
+    pub async fn create(channel_id: &str, id: i64, gallery_id: i32) -> Result<SqliteQueryResult> {
         sqlx::query!(
-            "INSERT OR IGNORE INTO poll (id, gallery_id, score) VALUES (?, ?, 0.0)",
+            "INSERT OR IGNORE INTO poll (id, gallery_id, channel_id, score) VALUES (?, ?, ?, 0.0)",
             id,
-            gallery_id
+            gallery_id,
+            channel_id,
         )
         .execute(&*DB)
         .await
     }
 
     #[tracing::instrument(level = Level::DEBUG)]
-    pub async fn get_by_gallery(gallery_id: i32) -> Result<Option<Self>> {
+    pub async fn get_by_gallery(channel_id: &str, gallery_id: i32) -> Result<Option<Self>> {
         sqlx::query_as!(
             Self,
-            r#"SELECT id, gallery_id as "gallery_id: i32", score as "score: f32", old_vote FROM poll WHERE gallery_id = ?"#,
-            gallery_id
+            r#"SELECT id, gallery_id as "gallery_id: i32", channel_id, score as "score: f32", old_vote FROM poll WHERE gallery_id = ? AND channel_id = ?"#,
+            gallery_id,
+            channel_id
         )
         .fetch_optional(&*DB)
         .await
     }
 
+    /// 将画廊在指定频道中的投票转移到另一个画廊，用于画廊发布新版本时保留原有的投票
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn transfer(channel_id: &str, from: i32, to: i32) -> Result<SqliteQueryResult> {
+        sqlx::query!(
+            "UPDATE poll SET gallery_id = ? WHERE gallery_id = ? AND channel_id = ?",
+            to,
+            from,
+            channel_id
+        )
+        .execute(&*DB)
+        .await
+    }
+
+    /// 为新画廊分配投票 ID，优先使用画廊 ID，已经被其他频道占用时使用一个新的 ID
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn allocate_id(gallery_id: i32) -> Result<i64> {
+        let id = gallery_id as i64;
+        let used = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM poll WHERE id = ?) as "used!: bool""#,
+            id
+        )
+        .fetch_one(&*DB)
+        .await?;
+        if !used {
+            return Ok(id);
+        }
+        sqlx::query_scalar!(r#"SELECT MAX(id) + 1 as "id!: i64" FROM poll"#).fetch_one(&*DB).await
+    }
+
     #[tracing::instrument(level = Level::DEBUG)]
     pub async fn get_vote(id: i64) -> Result<[i32; 5]> {
         let mut result = [0; 5];
@@ -83,11 +116,14 @@ impl PollEntity {
         Ok(score)
     }
 
-    /// 获取指定投票的分数排名区段，结果为一个 0~1 的小数
+    /// 获取指定投票在所属频道中的分数排名区段，结果为一个 0~1 的小数
     #[tracing::instrument(level = Level::DEBUG)]
     pub async fn rank(&self) -> Result<f32> {
         let record = sqlx::query!(
-            r#"SELECT COUNT(*) as "higher!: f32", (SELECT COUNT(*) FROM poll) as "total!: f32" FROM poll WHERE score > ?"#, self.score
+            r#"SELECT COUNT(*) as "higher!: f32", (SELECT COUNT(*) FROM poll WHERE channel_id IS ?) as "total!: f32" FROM poll WHERE score > ? AND channel_id IS ?"#,
+            self.channel_id,
+            self.score,
+            self.channel_id
         )
         .fetch_one(&*DB)
         .await?;
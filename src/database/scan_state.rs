@@ -0,0 +1,50 @@
+use chrono::{NaiveDateTime, Utc};
+use sqlx::sqlite::SqliteQueryResult;
+use sqlx::Result;
+use tracing::Level;
+
+use super::db::DB;
+
+/// 每个搜索配置的扫描进度
+#[derive(sqlx::FromRow, Debug)]
+pub struct ScanStateEntity {
+    /// 搜索配置的名称
+    pub profile: String,
+    /// 已经扫描过的最大画廊 ID，增量扫描遇到不大于它的画廊时停止翻页
+    pub max_gallery_id: i32,
+    pub updated_at: NaiveDateTime,
+}
+
+impl ScanStateEntity {
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn get(profile: &str) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                profile,
+                max_gallery_id as "max_gallery_id: i32",
+                updated_at
+            FROM scan_state WHERE profile = ?"#,
+            profile
+        )
+        .fetch_optional(&*DB)
+        .await
+    }
+
+    /// 更新扫描进度，只会往大的方向更新
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn update(profile: &str, max_gallery_id: i32) -> Result<SqliteQueryResult> {
+        let now = Utc::now().naive_utc();
+        sqlx::query!(
+            "INSERT INTO scan_state (profile, max_gallery_id, updated_at) VALUES (?, ?, ?)
+            ON CONFLICT (profile) DO UPDATE SET
+                max_gallery_id = MAX(max_gallery_id, excluded.max_gallery_id),
+                updated_at = excluded.updated_at",
+            profile,
+            max_gallery_id,
+            now,
+        )
+        .execute(&*DB)
+        .await
+    }
+}
@@ -0,0 +1,141 @@
+use chrono::{NaiveDateTime, Utc};
+use sqlx::sqlite::SqliteQueryResult;
+use sqlx::Result;
+use tracing::Level;
+
+use super::db::DB;
+use crate::ehentai::EhGalleryUrl;
+
+/// 上传任务所处的阶段
+#[derive(sqlx::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "lowercase")]
+pub enum UploadJobState {
+    /// 已加入队列，尚未开始
+    Queued,
+    /// 正在获取画廊信息
+    Resolving,
+    /// 正在上传图片
+    Uploading,
+    /// 正在发布文章和消息
+    Publishing,
+    /// 已完成
+    Done,
+    /// 已失败
+    Failed,
+}
+
+#[derive(sqlx::FromRow, Debug)]
+pub struct UploadJobEntity {
+    pub id: i64,
+    /// 画廊 ID
+    pub gallery_id: i32,
+    /// 画廊 token
+    pub token: String,
+    /// 是否跳过已上传的画廊和图片，为 false 时表示强制重新上传
+    pub check_exist: bool,
+    /// 发布的频道，为空时发布到默认频道
+    pub channel_id: Option<String>,
+    /// 消息回复的画廊，为空时回复父画廊
+    pub reply_to: Option<i32>,
+    /// 当前阶段
+    pub state: UploadJobState,
+    /// 失败原因
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl UploadJobEntity {
+    /// 将画廊加入上传队列，如果该画廊在同一个频道已经有未结束的任务，则返回已有的任务
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn push(
+        gallery: &EhGalleryUrl,
+        check_exist: bool,
+        channel_id: &str,
+        reply_to: Option<i32>,
+    ) -> Result<Self> {
+        let id = gallery.id();
+        let token = gallery.token();
+        let now = Utc::now().naive_utc();
+        sqlx::query!(
+            "INSERT OR IGNORE INTO upload_job (gallery_id, token, check_exist, channel_id, reply_to, state, created_at, updated_at) VALUES (?, ?, ?, ?, ?, 'queued', ?, ?)",
+            id,
+            token,
+            check_exist,
+            channel_id,
+            reply_to,
+            now,
+            now,
+        )
+        .execute(&*DB)
+        .await?;
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                id,
+                gallery_id as "gallery_id: i32",
+                token,
+                check_exist,
+                channel_id,
+                reply_to as "reply_to: i32",
+                state as "state: UploadJobState",
+                error,
+                created_at,
+                updated_at
+            FROM upload_job WHERE gallery_id = ? AND channel_id IS ? AND state NOT IN ('done', 'failed')"#,
+            id,
+            channel_id
+        )
+        .fetch_one(&*DB)
+        .await
+    }
+
+    /// 获取所有未结束的任务，按加入队列的顺序排列，被中断的任务也包含在内
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn list_unfinished() -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                id,
+                gallery_id as "gallery_id: i32",
+                token,
+                check_exist,
+                channel_id,
+                reply_to as "reply_to: i32",
+                state as "state: UploadJobState",
+                error,
+                created_at,
+                updated_at
+            FROM upload_job WHERE state NOT IN ('done', 'failed') ORDER BY id"#
+        )
+        .fetch_all(&*DB)
+        .await
+    }
+
+    /// 更新任务所处的阶段
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn update_state(id: i64, state: UploadJobState) -> Result<SqliteQueryResult> {
+        let now = Utc::now().naive_utc();
+        sqlx::query!("UPDATE upload_job SET state = ?, updated_at = ? WHERE id = ?", state, now, id)
+            .execute(&*DB)
+            .await
+    }
+
+    /// 将任务标记为失败，并记录失败原因
+    #[tracing::instrument(level = Level::DEBUG)]
+    pub async fn fail(id: i64, error: &str) -> Result<SqliteQueryResult> {
+        let now = Utc::now().naive_utc();
+        sqlx::query!(
+            "UPDATE upload_job SET state = 'failed', error = ?, updated_at = ? WHERE id = ?",
+            error,
+            now,
+            id
+        )
+        .execute(&*DB)
+        .await
+    }
+
+    pub fn url(&self) -> EhGalleryUrl {
+        format!("https://exhentai.org/g/{}/{}", self.gallery_id, self.token).parse().unwrap()
+    }
+}
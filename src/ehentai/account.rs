@@ -0,0 +1,260 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, Utc};
+use reqwest::Client;
+use tracing::{info, warn};
+
+use super::error::EhError;
+
+/// 连续多少次返回空白页面才认为 cookie 已失效，偶尔的空白页面可能只是代理或者 CDN 的问题
+const EXPIRE_AFTER_BLANK: u32 = 3;
+
+/// 账号当前的状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountHealth {
+    /// 正常
+    Ok,
+    /// 被封禁或者额度用完，冷却结束后重新启用
+    Limited { reason: String, until: NaiveDateTime },
+    /// cookie 失效，需要管理员更新配置文件
+    Expired,
+}
+
+impl std::fmt::Display for AccountHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ok => write!(f, "正常"),
+            Self::Limited { reason, until } => write!(f, "受限至 {}（{}）", until, reason),
+            Self::Expired => write!(f, "cookie 已失效"),
+        }
+    }
+}
+
+/// 一个 E 站账号，每个账号有自己的 cookie 和代理
+#[derive(Debug)]
+pub struct EhAccount {
+    name: String,
+    /// 为 None 时表示使用全局代理，IP 被封禁时使用同一个代理的账号会一起停用
+    proxy: Option<String>,
+    client: Client,
+    health: Mutex<AccountHealth>,
+    /// 连续返回空白页面的次数
+    blank_pages: AtomicU32,
+}
+
+impl EhAccount {
+    pub fn new(name: &str, proxy: Option<&str>, client: Client) -> Self {
+        Self {
+            name: name.to_string(),
+            proxy: proxy.map(str::to_string),
+            client,
+            health: Mutex::new(AccountHealth::Ok),
+            blank_pages: AtomicU32::new(0),
+        }
+    }
+}
+
+/// 账号池，按顺序使用第一个可用的账号，当前账号受限时自动切换到下一个
+#[derive(Debug)]
+pub struct AccountPool {
+    accounts: Vec<EhAccount>,
+    current: AtomicUsize,
+    cooldown: Duration,
+    /// 尚未通知管理员的状态变化
+    events: Mutex<Vec<String>>,
+}
+
+impl AccountPool {
+    pub fn new(accounts: Vec<EhAccount>, cooldown: Duration) -> Self {
+        Self { accounts, current: AtomicUsize::new(0), cooldown, events: Default::default() }
+    }
+
+    /// 选择一个可用的账号，返回其序号和客户端，所有账号都不可用时返回 None
+    pub fn select(&self) -> Option<(usize, Client)> {
+        let now = Utc::now().naive_utc();
+        let start = self.current.load(Ordering::Relaxed);
+        for offset in 0..self.accounts.len() {
+            let index = (start + offset) % self.accounts.len();
+            if self.available(index, now) {
+                if offset != 0 {
+                    info!("切换到账号 {}", self.accounts[index].name);
+                    self.current.store(index, Ordering::Relaxed);
+                }
+                return Some((index, self.accounts[index].client.clone()));
+            }
+        }
+        None
+    }
+
+    /// 当前正在使用的账号序号
+    pub fn current(&self) -> usize {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    /// 根据请求错误停用账号
+    ///
+    /// 连续多次返回空白页面时认为 cookie 失效并永久停用，其他情况在冷却时间之后重新启用。
+    /// IP 被封禁时，使用同一个代理的其他账号也无法访问，因此一起停用
+    pub fn mark(&self, index: usize, err: &EhError) {
+        let until = Utc::now().naive_utc() + self.cooldown;
+        let health = match err {
+            EhError::SadPanda => {
+                let count = self.accounts[index].blank_pages.fetch_add(1, Ordering::Relaxed) + 1;
+                if count >= EXPIRE_AFTER_BLANK {
+                    AccountHealth::Expired
+                } else {
+                    AccountHealth::Limited { reason: format!("连续 {} 次返回空白页面", count), until }
+                }
+            }
+            err => AccountHealth::Limited { reason: err.to_string(), until },
+        };
+        let proxy = &self.accounts[index].proxy;
+        for (i, account) in self.accounts.iter().enumerate() {
+            let affected =
+                i == index || matches!(err, EhError::IpBanned(_)) && &account.proxy == proxy;
+            if !affected {
+                continue;
+            }
+            let mut current = account.health.lock().unwrap();
+            // cookie 失效的账号只能通过请求成功或者重新加载配置恢复，不会被改成冷却状态
+            if *current == AccountHealth::Expired {
+                continue;
+            }
+            if *current == AccountHealth::Ok {
+                warn!("账号 {} 已停用：{}", account.name, health);
+                self.events
+                    .lock()
+                    .unwrap()
+                    .push(format!("账号 {} 已停用：{}", account.name, health));
+            }
+            *current = health.clone();
+        }
+    }
+
+    /// 请求成功，清空空白页面的计数，之前被认为 cookie 失效的账号也恢复正常
+    pub fn mark_ok(&self, index: usize) {
+        let account = &self.accounts[index];
+        account.blank_pages.store(0, Ordering::Relaxed);
+        let mut health = account.health.lock().unwrap();
+        if *health == AccountHealth::Expired {
+            *health = AccountHealth::Ok;
+        }
+    }
+
+    /// 所有账号的名称和状态
+    pub fn status(&self) -> Vec<(String, AccountHealth)> {
+        let now = Utc::now().naive_utc();
+        (0..self.accounts.len())
+            .map(|i| {
+                self.available(i, now);
+                let account = &self.accounts[i];
+                (account.name.clone(), account.health.lock().unwrap().clone())
+            })
+            .collect()
+    }
+
+    /// 取出尚未通知的状态变化
+    pub fn take_events(&self) -> Vec<String> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+
+    /// 账号是否可用，冷却结束的账号会在这里重新启用
+    fn available(&self, index: usize, now: NaiveDateTime) -> bool {
+        let account = &self.accounts[index];
+        let mut health = account.health.lock().unwrap();
+        match &*health {
+            AccountHealth::Ok => true,
+            AccountHealth::Limited { until, .. } if *until <= now => {
+                info!("账号 {} 冷却结束，重新启用", account.name);
+                self.events
+                    .lock()
+                    .unwrap()
+                    .push(format!("账号 {} 冷却结束，重新启用", account.name));
+                *health = AccountHealth::Ok;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(proxies: &[Option<&str>], cooldown: Duration) -> AccountPool {
+        let accounts = proxies
+            .iter()
+            .enumerate()
+            .map(|(i, proxy)| EhAccount::new(&format!("a{}", i), *proxy, Client::new()))
+            .collect();
+        AccountPool::new(accounts, cooldown)
+    }
+
+    #[test]
+    fn rotate_accounts() {
+        let pool = pool(&[None, None, Some("socks5://127.0.0.1:1080")], Duration::from_secs(3600));
+        assert_eq!(pool.select().unwrap().0, 0);
+
+        pool.mark(0, &EhError::BandwidthExceeded);
+        assert_eq!(pool.select().unwrap().0, 1);
+        assert_eq!(pool.current(), 1);
+
+        // 同一个代理的账号一起停用
+        pool.mark(1, &EhError::IpBanned("banned".into()));
+        assert_eq!(pool.select().unwrap().0, 2);
+
+        for _ in 0..EXPIRE_AFTER_BLANK {
+            pool.mark(2, &EhError::SadPanda);
+        }
+        assert!(pool.select().is_none());
+        assert_eq!(pool.status()[2].1, AccountHealth::Expired);
+        assert_eq!(pool.take_events().len(), 3);
+        assert!(pool.take_events().is_empty());
+    }
+
+    #[test]
+    fn recover_after_cooldown() {
+        let pool = pool(&[None, None], Duration::ZERO);
+        pool.mark(0, &EhError::BandwidthExceeded);
+        for _ in 0..EXPIRE_AFTER_BLANK {
+            pool.mark(1, &EhError::SadPanda);
+        }
+        // 冷却时间为 0，第一个账号立即恢复，cookie 失效的账号不会恢复
+        assert_eq!(pool.select().unwrap().0, 0);
+        assert_eq!(pool.status()[1].1, AccountHealth::Expired);
+        assert_eq!(pool.take_events().len(), 3);
+    }
+
+    #[test]
+    fn ip_ban_keeps_expired() {
+        let pool = pool(&[None, None], Duration::ZERO);
+        for _ in 0..EXPIRE_AFTER_BLANK {
+            pool.mark(0, &EhError::SadPanda);
+        }
+        // 同一个代理被封禁时，cookie 失效的账号不会变成冷却状态，冷却结束后也不会被重新使用
+        pool.mark(1, &EhError::IpBanned("banned".into()));
+        assert_eq!(pool.status()[0].1, AccountHealth::Expired);
+        assert_eq!(pool.select().unwrap().0, 1);
+        pool.mark_ok(0);
+        assert_eq!(pool.status()[0].1, AccountHealth::Ok);
+    }
+
+    #[test]
+    fn blank_page_not_expired() {
+        let pool = pool(&[None], Duration::ZERO);
+        // 偶尔的空白页面只会暂时停用，请求成功后重新计数
+        pool.mark(0, &EhError::SadPanda);
+        pool.mark(0, &EhError::SadPanda);
+        assert_eq!(pool.select().unwrap().0, 0);
+        pool.mark_ok(0);
+        pool.mark(0, &EhError::SadPanda);
+        assert_eq!(pool.select().unwrap().0, 0);
+        for _ in 0..EXPIRE_AFTER_BLANK {
+            pool.mark(0, &EhError::SadPanda);
+        }
+        assert!(pool.select().is_none());
+    }
+}
@@ -1,19 +1,24 @@
-use chrono::prelude::*;
+use anyhow::bail;
 use futures::prelude::*;
-use indexmap::IndexMap;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use reqwest::header::*;
-use reqwest::Client;
-use scraper::{Html, Selector};
-use serde::Serialize;
+use reqwest::{Client, RequestBuilder};
+use chrono::NaiveDateTime;
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::{debug, error, info, warn, Instrument};
 
+use super::account::*;
 use super::error::*;
 use super::types::*;
+use crate::config::ExHentai;
 use crate::utils::html::SelectorExtend;
+use crate::utils::http::{ClientKind, HttpFactory};
 
 /// 带指数退避的重试机制  
 async fn retry_request<T, F, Fut>(max_retries: usize, mut func: F) -> Result<T>
@@ -27,7 +32,8 @@ where
             Ok(result) => return Ok(result),
             Err(err) => {
                 attempts += 1;
-                if attempts >= max_retries {
+                // 被封禁或者画廊已删除时，重试没有意义
+                if attempts >= max_retries || err.is_blocked() || matches!(err, EhError::GalleryRemoved(_)) {
                     return Err(err);
                 }
                 
@@ -53,19 +59,148 @@ macro_rules! send {
     };
 }
 
+/// 发送请求并读取响应内容，同时识别 E 站返回的各种异常页面
+async fn fetch_text(req: RequestBuilder) -> Result<String> {
+    let resp = req.send().await?;
+    let url = resp.url().to_string();
+    let status = resp.error_for_status_ref().err();
+    let text = resp.text().await?;
+    detect_error(&url, &text)?;
+    match status {
+        Some(e) => Err(e.into()),
+        None => Ok(text),
+    }
+}
+
+/// 识别 E 站返回的异常页面，这些页面的状态码不一定是错误码
+///
+/// 画廊页面带有评论，评论中可能会引用这些提示，因此只匹配标题，或者没有画廊内容的页面
+fn detect_error(url: &str, text: &str) -> Result<()> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        // cookie 失效或者没有里站权限时，里站会返回一个空白页面（显示为熊猫图）
+        return Err(EhError::SadPanda);
+    }
+    if trimmed.starts_with("Your IP address has been temporarily banned") {
+        return Err(EhError::IpBanned(trimmed.lines().next().unwrap_or(trimmed).to_string()));
+    }
+    if title(trimmed).is_some_and(|t| t.starts_with("Gallery Not Available")) {
+        return Err(EhError::GalleryRemoved(url.to_string()));
+    }
+    if has_gallery_markup(trimmed) {
+        return Ok(());
+    }
+    if trimmed.contains("This gallery has been removed or is unavailable") {
+        return Err(EhError::GalleryRemoved(url.to_string()));
+    }
+    if trimmed.contains("You have exceeded your image viewing limits") {
+        return Err(EhError::BandwidthExceeded);
+    }
+    Ok(())
+}
+
+/// 页面的 <title>
+fn title(text: &str) -> Option<&str> {
+    let start = text.find("<title>")? + "<title>".len();
+    let end = text[start..].find("</title>")?;
+    Some(text[start..start + end].trim())
+}
+
+/// 是否为正常的画廊、图片或者搜索页面
+fn has_gallery_markup(text: &str) -> bool {
+    ["id=\"gn\"", "id=\"gdt\"", "id=\"cdiv\"", "id=\"i1\"", "class=\"itg"]
+        .iter()
+        .any(|m| text.contains(m))
+}
+
+/// 从 home.php 中解析图片额度，格式为 You are currently at <strong>123</strong> towards a limit of <strong>5,000</strong>
+fn parse_quota(text: &str) -> Option<EhQuota> {
+    static RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"currently at\s*<strong>(?P<used>[\d,]+)</strong>[^<]*limit of\s*<strong>(?P<limit>[\d,]+)</strong>")
+            .unwrap()
+    });
+    let captures = RE.captures(text)?;
+    let number = |name| captures.name(name)?.as_str().replace(',', "").parse().ok();
+    Some(EhQuota { used: number("used")?, limit: number("limit")? })
+}
+
+/// 图片额度用完时，E 站会把图片替换为一张 509 提示图
+pub fn check_image_url(url: &str) -> Result<()> {
+    let name = url.rsplit('/').next().unwrap_or_default();
+    if name == "509.gif" || name == "509s.gif" {
+        return Err(EhError::BandwidthExceeded);
+    }
+    Ok(())
+}
+
 macro_rules! selector {
     ($selector:tt) => {
         Selector::parse($selector).unwrap()
     };
 }
 
+/// gdata 每次请求最多查询的画廊数量
+const GDATA_BATCH_SIZE: usize = 25;
+
+#[derive(Debug, Deserialize)]
+struct GDataResponse {
+    gmetadata: Vec<GDataItem>,
+}
+
+/// 画廊不存在或者 token 错误时，对应的条目只有 gid 和 error
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum GDataItem {
+    Ok(Box<EhGalleryMeta>),
+    Err { gid: i32, error: String },
+}
+
+/// imagedispatch 的返回值，只列出了用到的字段
+#[derive(Debug, Deserialize)]
+struct ImageDispatch {
+    /// 缩放过的图片地址
+    i: String,
+    /// 原图的相对地址
+    #[serde(default)]
+    lf: String,
+    /// 图片所在的 H@H 节点，换节点时作为 nl 参数
+    s: serde_json::Value,
+}
+
 #[derive(Debug, Clone)]
-pub struct EhClient(pub Client);
+pub struct EhClient(Arc<AccountPool>);
 
 impl EhClient {
-    #[tracing::instrument(skip(cookie))]
-    pub async fn new(cookie: &str) -> Result<Self> {
-        info!("登陆 E 站中");
+    #[tracing::instrument(skip(http, config))]
+    pub async fn new(http: &HttpFactory, config: &ExHentai) -> anyhow::Result<Self> {
+        let accounts = config.accounts();
+        if accounts.is_empty() {
+            bail!("至少需要配置一个 E 站账号");
+        }
+
+        let mut pool = vec![];
+        let mut failed = vec![];
+        for (index, account) in accounts.iter().enumerate() {
+            let client = Self::build_client(&http.with_proxy(account.proxy.as_deref()), &account.cookie)?;
+            info!("登陆 E 站中: {}", account.name);
+            if let Err(e) = Self::login(&client).await {
+                error!("账号 {} 登陆失败: {}", account.name, e);
+                failed.push((index, e));
+            }
+            pool.push(EhAccount::new(&account.name, account.proxy.as_deref(), client));
+        }
+        if failed.len() == accounts.len() {
+            bail!("所有账号都无法登陆: {}", failed[0].1);
+        }
+
+        let pool = AccountPool::new(pool, config.account_cooldown);
+        for (index, err) in &failed {
+            pool.mark(*index, err);
+        }
+        Ok(Self(Arc::new(pool)))
+    }
+
+    fn build_client(http: &HttpFactory, cookie: &str) -> anyhow::Result<Client> {
         // 将 cookie 日志级别改为 debug，避免在生产环境泄露敏感信息
         debug!("cookie: {}", cookie);
         let headers = headers! {
@@ -74,26 +209,57 @@ impl EhClient {
             ACCEPT_LANGUAGE => "zh-CN,zh;q=0.9,en;q=0.8",
             CACHE_CONTROL => "max-age=0",
             CONNECTION => "keep-alive",
-            HOST => "exhentai.org",
-            REFERER => "https://exhentai.org",
+            REFERER => site_url(),
             UPGRADE_INSECURE_REQUESTS => "1",
-            USER_AGENT => "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
             COOKIE => cookie
         };
+        Ok(http.builder(ClientKind::Site)?.cookie_store(true).default_headers(headers).build()?)
+    }
+
+    /// 获取必要的 cookie，同时检查 cookie 是否有效
+    async fn login(client: &Client) -> Result<()> {
+        let _response = send!(client.get(format!("{}/uconfig.php", site_url())))?;
+        let text = fetch_text(client.get(format!("{}/mytags", site_url()))).await?;
+        debug!("mytags: {}", text);
+        Ok(())
+    }
+
+    /// 当前可用的账号的客户端
+    fn client(&self) -> Result<Client> {
+        self.0.select().map(|(_, client)| client).ok_or(EhError::NoAvailableAccount)
+    }
+
+    /// 使用当前账号发送请求，账号受限时停用该账号，并换下一个账号重试
+    async fn fetch(&self, req: impl Fn(&Client) -> RequestBuilder) -> Result<String> {
+        loop {
+            let (index, client) = self.0.select().ok_or(EhError::NoAvailableAccount)?;
+            match fetch_text(req(&client)).await {
+                Err(e) if e.is_blocked() => self.0.mark(index, &e),
+                Ok(text) => {
+                    self.0.mark_ok(index);
+                    return Ok(text);
+                }
+                result => return result,
+            }
+        }
+    }
 
-        let client = Client::builder()
-            .cookie_store(true)
-            .default_headers(headers)
-            .timeout(Duration::from_secs(30))
-            .connect_timeout(Duration::from_secs(30))
-            .build()?;
+    /// 报告在 EhClient 之外发现的账号问题（比如下载图片时遇到 509），返回是否还有可用的账号
+    pub fn report(&self, err: &EhError) -> bool {
+        if err.is_blocked() && !matches!(err, EhError::NoAvailableAccount) {
+            self.0.mark(self.0.current(), err);
+        }
+        self.0.select().is_some()
+    }
 
-        // 获取必要的 cookie
-        let _response = send!(client.get("https://exhentai.org/uconfig.php"))?;
-        let _response = send!(client.get("https://exhentai.org/mytags"))?;
-        debug!("mytags: {}", _response.text().await?);
+    /// 所有账号的名称和状态
+    pub fn accounts(&self) -> Vec<(String, AccountHealth)> {
+        self.0.status()
+    }
 
-        Ok(Self(client))
+    /// 取出尚未通知管理员的账号状态变化
+    pub fn take_account_events(&self) -> Vec<String> {
+        self.0.take_events()
     }
 
     /// 访问指定页面，返回画廊列表
@@ -104,8 +270,8 @@ impl EhClient {
         params: &T,
         next: &str,
     ) -> Result<(Vec<EhGalleryUrl>, Option<String>)> {
-        let resp = send!(self.0.get(url).query(params).query(&[("next", next)]))?;
-        let html = Html::parse_document(&resp.text().await?);
+        let text = self.fetch(|c| c.get(url).query(params).query(&[("next", next)])).await?;
+        let html = Html::parse_document(&text);
 
         let selector = selector!("table.itg.gltc tr");
         let gl_list = html.select(&selector);
@@ -132,7 +298,7 @@ impl EhClient {
         &'a self,
         params: &'a T,
     ) -> impl Stream<Item = EhGalleryUrl> + 'a {
-        self.page_iter("https://exhentai.org", params)
+        self.page_iter(site_url(), params)
     }
 
     /// 获取指定页面的画廊列表，返回一个异步迭代器
@@ -167,56 +333,75 @@ impl EhClient {
     pub async fn archive_gallery(&self, url: &EhGalleryUrl) -> Result<()> {
         static RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"or=(?P<or>[0-9a-z-]+)").unwrap());
 
-        let resp = send!(self.0.get(url.url()))?;
-        let html = Html::parse_document(&resp.text().await?);
+        let html = Html::parse_document(&self.fetch(|c| c.get(url.url())).await?);
         let onclick = html.select_attr("p.g2 a", "onclick").unwrap();
 
         let or = RE.captures(&onclick).and_then(|c| c.name("or")).unwrap().as_str();
 
         send!(self
-            .0
-            .post("https://exhentai.org/archiver.php")
+            .client()?
+            .post(format!("{}/archiver.php", site_url()))
             .query(&[("gid", &*url.id().to_string()), ("token", url.token()), ("or", or)])
             .form(&[("hathdl_xres", "org")]))?;
 
         Ok(())
     }
 
+    /// 获取当前的图片额度使用情况
+    #[tracing::instrument(skip(self))]
+    pub async fn get_quota(&self) -> Result<EhQuota> {
+        // NOTE: 里站没有 home.php，需要到表站查询
+        let home = match site_url() {
+            "https://exhentai.org" => "https://e-hentai.org/home.php".to_string(),
+            site => format!("{}/home.php", site),
+        };
+        let text = self.fetch(|c| c.get(&home)).await?;
+        parse_quota(&text).ok_or_else(|| EhError::ApiError("无法解析图片额度".to_string()))
+    }
+
+    /// 通过 API 批量获取画廊的元数据，不存在的画廊会被忽略
+    #[tracing::instrument(skip(self))]
+    pub async fn get_gallery_meta(&self, urls: &[EhGalleryUrl]) -> Result<Vec<EhGalleryMeta>> {
+        let mut ret = vec![];
+        for chunk in urls.chunks(GDATA_BATCH_SIZE) {
+            let gidlist = chunk.iter().map(|u| (u.id(), u.token())).collect::<Vec<_>>();
+            let body = serde_json::json!({ "method": "gdata", "gidlist": gidlist, "namespace": 1 });
+            let resp = retry_request(3, || self.api::<GDataResponse>(&body)).await?;
+            for item in resp.gmetadata {
+                match item {
+                    GDataItem::Ok(meta) => ret.push(*meta),
+                    GDataItem::Err { gid, error } => warn!("获取画廊 {} 的元数据失败: {}", gid, error),
+                }
+            }
+        }
+        Ok(ret)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn get_gallery(&self, url: &EhGalleryUrl) -> Result<EhGallery> {
+        // 标题、标签等信息通过 API 获取，页面只用来获取收藏数和页面列表
+        let meta = self
+            .get_gallery_meta(std::slice::from_ref(url))
+            .await?
+            .pop()
+            .ok_or_else(|| EhError::GalleryRemoved(url.url()))?;
+
         // NOTE: 由于 Html 是 !Send 的，为了避免它被包含在 Future 上下文中，这里将它放在一个单独的作用域内
         // 参见：https://rust-lang.github.io/async-book/07_workarounds/03_send_approximation.html
-        let (title, title_jp, parent, tags, favorite, mut pages, posted, mut next_page) = {
-            let resp = send!(self.0.get(url.url()))?;
-            let html = Html::parse_document(&resp.text().await?);
-
-            // 英文标题、日文标题、父画廊
-            let title = html.select_text("h1#gn").expect("xpath fail: h1#gn");
-            let title_jp = html.select_text("h1#gj");
-            let parent = html.select_attr("td.gdt2 a", "href").and_then(|s| s.parse().ok());
-
-            // 画廊 tag
-            let mut tags = IndexMap::new();
-            let selector = selector!("div#taglist tr");
-            for ele in html.select(&selector) {
-                let namespace = ele
-                    .select_text("td.tc")
-                    .expect("xpath fail: td.tc")
-                    .trim_matches(':')
-                    .to_string();
-                let tag = ele.select_texts("td div a");
-                tags.insert(namespace, tag);
-            }
-
-            // 收藏数量
-            let favorite = html.select_text("#favcount").expect("xpath fail: #favcount");
-            let favorite = favorite.split(' ').next()
-                .and_then(|s| s.parse().ok())
+        let (favorite, language, rating_count, newer_versions, comments, mut pages, mut next_page) = {
+            // hc=1 时显示所有评论，否则只显示得分最高的几条
+            let html = Html::parse_document(&self.fetch(|c| c.get(url.url()).query(&[("hc", "1")])).await?);
+
+            // 收藏数量，API 中没有这一项
+            let favorite = html
+                .select_text("#favcount")
+                .and_then(|s| s.split(' ').next().and_then(|s| s.parse().ok()))
                 .unwrap_or(0);
 
-            // 发布时间
-            let posted = &html.select_texts("td.gdt2")[0];
-            let posted = NaiveDateTime::parse_from_str(posted, "%Y-%m-%d %H:%M")?;
+            // 语言和评分人数，API 中也没有
+            let language = parse_language(&html);
+            let rating_count =
+                html.select_text("#rating_count").and_then(|s| s.parse().ok()).unwrap_or(0);
 
             // 每一页的 URL
             let pages = html.select_attrs("div#gdt a", "href");
@@ -224,13 +409,15 @@ impl EhClient {
             // 下一页的 URL
             let next_page = html.select_attr("table.ptb td:last-child a", "href");
 
-            (title, title_jp, parent, tags, favorite, pages, posted, next_page)
+            let newer_versions = parse_newer_versions(&html);
+            let comments = parse_comments(&html);
+
+            (favorite, language, rating_count, newer_versions, comments, pages, next_page)
         };
 
         while let Some(next_page_url) = &next_page {
             debug!(next_page_url);
-            let resp = send!(self.0.get(next_page_url))?;
-            let html = Html::parse_document(&resp.text().await?);
+            let html = Html::parse_document(&self.fetch(|c| c.get(next_page_url)).await?);
             // 每一页的 URL
             pages.extend(html.select_attrs("div#gdt a", "href"));
             // 下一页的 URL
@@ -244,25 +431,44 @@ impl EhClient {
 
         Ok(EhGallery {
             url: url.clone(),
-            title,
-            title_jp,
-            parent,
-            tags,
+            title: meta.title.clone(),
+            title_jp: meta.title_jp(),
+            parent: meta.parent(),
+            tags: meta.tags(),
             favorite,
             pages,
-            posted,
+            posted: meta.posted(),
             cover,
+            category: meta.category.clone(),
+            language,
+            uploader: meta.uploader.clone(),
+            filesize: meta.filesize,
+            rating: meta.rating,
+            rating_count,
+            newer_versions,
+            expunged: meta.expunged,
+            comments,
         })
     }
 
-    /// 获取画廊的某一页的图片的 fileindex 和实际地址和 nl
+    /// 获取画廊的新版本和评论，只请求画廊的第一页
     #[tracing::instrument(skip(self))]
-    pub async fn get_image_url(&self, page: &EhPageUrl) -> Result<(u32, String)> {
-        let resp = retry_request(3, || async {
-            send!(self.0.get(&page.url())).map_err(|e| e.into())
-        }).await?;
+    pub async fn get_gallery_page(&self, url: &EhGalleryUrl) -> Result<EhGalleryPage> {
+        let text = self.fetch(|c| c.get(url.url()).query(&[("hc", "1")])).await?;
+        let html = Html::parse_document(&text);
+        Ok(EhGalleryPage {
+            newer_versions: parse_newer_versions(&html),
+            comments: parse_comments(&html),
+            rating_count: html.select_text("#rating_count").and_then(|s| s.parse().ok()),
+        })
+    }
+
+    /// 获取画廊的某一页的图片的 fileindex 和实际地址
+    #[tracing::instrument(skip(self))]
+    pub async fn get_image_url(&self, page: &EhPageUrl) -> Result<EhImage> {
+        let text = retry_request(3, || self.fetch(|c| c.get(page.url()))).await?;
         let (original_url, url, nl, fileindex) = {
-            let html = Html::parse_document(&resp.text().await?);
+            let html = Html::parse_document(&text);
 
             // 优先尝试获取原图链接 (div#i6 div a[href*="fullimg"])
             let original_url = html
@@ -277,15 +483,18 @@ impl EhClient {
             (original_url, url, nl, fileindex)
         };
 
+        // 没有原图链接时，页面上显示的就是原图
+        let original = original_url.is_none();
+
         // 优先使用原图链接
         if let Some(original_url) = original_url {
             debug!("发现原图链接: {}", original_url);
             // 获取302跳转后的真实URL
-            match self.0.get(&original_url).send().await {
-                Ok(resp) => {
-                    let final_url = resp.url().to_string();
+            match self.resolve_original(&original_url).await {
+                Ok(final_url) => {
                     debug!("原图跳转后的URL: {}", final_url);
-                    return Ok((fileindex, final_url));
+                    check_image_url(&final_url)?;
+                    return Ok(EhImage { fileindex, url: final_url, original: true, preview: Some(url) });
                 }
                 Err(e) => {
                     debug!("原图链接请求失败: {}, 降级使用普通图片", e);
@@ -294,7 +503,93 @@ impl EhClient {
             }
         }
 
-        self.fallback_to_normal_image(page, url, nl, fileindex).await
+        self.fallback_to_normal_image(page, url, nl, fileindex, original).await
+    }
+
+    /// 获取画廊的多页查看器信息，账号没有 MPV 权限时会返回错误
+    #[tracing::instrument(skip(self))]
+    pub async fn get_mpv(&self, url: &EhGalleryUrl) -> Result<EhMpv> {
+        static RE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r#"var mpvkey = "(?P<key>[0-9a-z]+)";"#).unwrap());
+        let mpv_url = format!("{}/mpv/{}/{}/", site_url(), url.id(), url.token());
+        let text = self.fetch(|c| c.get(&mpv_url)).await?;
+        let mpvkey = RE
+            .captures(&text)
+            .and_then(|c| c.name("key"))
+            .ok_or_else(|| EhError::ApiError(format!("无法获取 mpvkey: {}", mpv_url)))?
+            .as_str()
+            .to_string();
+        Ok(EhMpv { gid: url.id(), mpvkey })
+    }
+
+    /// 通过 MPV 的 imagedispatch API 获取图片地址
+    ///
+    /// 和 get_image_url 相比不需要解析页面，并且一次请求就能同时拿到原图和缩放图的地址
+    #[tracing::instrument(skip(self, mpv))]
+    pub async fn get_image_url_mpv(&self, mpv: &EhMpv, page: &EhPageUrl) -> Result<EhImage> {
+        let dispatch = self.image_dispatch(mpv, page, None).await?;
+        check_image_url(&dispatch.i)?;
+        let fileindex = extract_fileindex(&dispatch.i)
+            .ok_or_else(|| EhError::ApiError(format!("无法解析 fileindex: {}", dispatch.i)))?;
+
+        // 原图链接需要登录，和 get_image_url 一样先获取跳转后的地址
+        if !dispatch.lf.is_empty() {
+            let url = format!("{}/{}", site_url(), dispatch.lf.trim_start_matches('/'));
+            match self.resolve_original(&url).await {
+                Ok(url) => {
+                    check_image_url(&url)?;
+                    return Ok(EhImage { fileindex, url, original: true, preview: Some(dispatch.i) });
+                }
+                Err(e) => {
+                    debug!("原图链接请求失败: {}, 降级使用普通图片", e);
+                    return Ok(EhImage { fileindex, url: dispatch.i, original: false, preview: None });
+                }
+            }
+        }
+        // 没有原图链接时拿到的是缩放后的图片，与页面 hash 对应不上
+        if send!(self.client()?.head(&dispatch.i)).is_ok() {
+            return Ok(EhImage { fileindex, url: dispatch.i, original: false, preview: None });
+        }
+
+        // 当前节点无法访问时，带上 nl 参数换一个节点
+        let nl = match dispatch.s {
+            serde_json::Value::String(s) => s,
+            v => v.to_string(),
+        };
+        let dispatch = self.image_dispatch(mpv, page, Some(&nl)).await?;
+        Ok(EhImage { fileindex, url: dispatch.i, original: false, preview: None })
+    }
+
+    /// 获取原图链接跳转后的真实地址，使用 HEAD 请求，避免为了拿到地址把原图完整下载一遍
+    async fn resolve_original(&self, url: &str) -> Result<String> {
+        let resp = send!(self.client()?.head(url))?;
+        Ok(resp.url().to_string())
+    }
+
+    async fn image_dispatch(
+        &self,
+        mpv: &EhMpv,
+        page: &EhPageUrl,
+        nl: Option<&str>,
+    ) -> Result<ImageDispatch> {
+        let mut body = serde_json::json!({
+            "method": "imagedispatch",
+            "gid": mpv.gid,
+            "page": page.page(),
+            "imgkey": page.hash(),
+            "mpvkey": mpv.mpvkey,
+        });
+        if let Some(nl) = nl {
+            body["nl"] = nl.into();
+        }
+        self.api(&body).await
+    }
+
+    /// 调用 api.php
+    async fn api<T: DeserializeOwned>(&self, body: &serde_json::Value) -> Result<T> {
+        let api = format!("{}/api.php", site_url());
+        let text = self.fetch(|c| c.post(&api).json(body)).await?;
+        serde_json::from_str(&text).map_err(|e| EhError::ApiError(format!("{}: {}", e, text)))
     }
 
     /// 降级使用普通图片
@@ -304,20 +599,83 @@ impl EhClient {
         url: String,
         nl: Option<String>,
         fileindex: u32,
-    ) -> Result<(u32, String)> {
-        if send!(self.0.head(&url)).is_ok() {
-            Ok((fileindex, url))
-        } else if nl.is_some() {
-            let resp = send!(self.0.get(&page.with_nl(&nl.unwrap()).url()))?;
-            let html = Html::parse_document(&resp.text().await?);
+        original: bool,
+    ) -> Result<EhImage> {
+        check_image_url(&url)?;
+        if send!(self.client()?.head(&url)).is_ok() {
+            Ok(EhImage { fileindex, url, original, preview: None })
+        } else if let Some(nl) = nl {
+            let text = self.fetch(|c| c.get(page.with_nl(&nl).url())).await?;
+            let html = Html::parse_document(&text);
             let url = html.select_attr("img#img", "src").unwrap();
-            Ok((fileindex, url))
+            check_image_url(&url)?;
+            Ok(EhImage { fileindex, url, original, preview: None })
         } else {
             Err(EhError::HaHUrlBroken(url))
         }
     }
 }
 
+/// 画廊页面顶部的 "There are newer versions of this gallery available" 中列出的新版本，按发布时间排列
+fn parse_newer_versions(html: &Html) -> Vec<EhGalleryUrl> {
+    html.select_attrs("#gnd a", "href").into_iter().filter_map(|s| s.parse().ok()).collect()
+}
+
+/// 画廊页面底部的评论，结构为
+/// <div class="c1"><div class="c3">Posted on 19 May 2023, 08:00 by: <a>name</a></div>
+/// <div class="c5"><span>+12</span></div><div class="c6" id="comment_123">...</div></div>
+fn parse_comments(html: &Html) -> Vec<EhComment> {
+    static RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"Posted on (?P<time>.+?) by:").unwrap());
+    let comments = selector!("#cdiv .c1");
+    let header = selector!(".c3");
+    let author = selector!(".c3 a");
+    let score = selector!(".c5 span");
+    let content = selector!(".c6");
+    html.select(&comments)
+        .filter_map(|c| {
+            let content = c.select(&content).next()?;
+            let id = content.value().attr("id")?.strip_prefix("comment_")?.parse().ok()?;
+            let header = c.select(&header).next()?.text().collect::<String>();
+            let time = RE.captures(&header)?.name("time")?.as_str().to_string();
+            let posted = NaiveDateTime::parse_from_str(&time, "%d %B %Y, %H:%M").ok()?;
+            let author = c.select(&author).next().map(|a| a.text().collect()).unwrap_or_default();
+            let score = c
+                .select(&score)
+                .next()
+                .and_then(|s| s.text().collect::<String>().trim_start_matches('+').parse().ok())
+                .unwrap_or(0);
+            Some(EhComment { id, author, score, content: element_text(content), posted })
+        })
+        .collect()
+}
+
+/// 元素内的文本，<br> 会被转换为换行
+fn element_text(element: ElementRef) -> String {
+    let mut text = String::new();
+    for node in element.descendants() {
+        match node.value() {
+            Node::Text(t) => text.push_str(t),
+            Node::Element(e) if e.name() == "br" => text.push('\n'),
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+/// 画廊信息表中的语言，格式为 <td class="gdt1">Language:</td><td class="gdt2">Chinese &nbsp;<span>TR</span></td>
+fn parse_language(html: &Html) -> Option<String> {
+    let rows = selector!("#gdd tr");
+    let cells = selector!("td");
+    html.select(&rows).find_map(|row| {
+        let mut cells = row.select(&cells);
+        if cells.next()?.text().collect::<String>().trim() != "Language:" {
+            return None;
+        }
+        let value = cells.next()?.text().next()?.trim().to_string();
+        Some(value).filter(|s| !s.is_empty() && s != "N/A")
+    })
+}
+
 fn extract_fileindex(url: &str) -> Option<u32> {
     static RE1: Lazy<Regex> = Lazy::new(|| Regex::new(r"fileindex=(?P<fileindex>\d+)").unwrap());
     static RE2: Lazy<Regex> = Lazy::new(|| Regex::new(r"/om/(?P<fileindex>\d+)/").unwrap());
@@ -331,3 +689,90 @@ fn extract_nl(onerror: String) -> Option<String> {
     let captures = RE.captures(&onerror)?;
     Some(captures.name("nl")?.as_str().to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_error_page() {
+        let url = "https://exhentai.org/g/1/abc/";
+        assert!(matches!(detect_error(url, "  \n"), Err(EhError::SadPanda)));
+        let ban = "Your IP address has been temporarily banned for excessive pageloads. The ban expires in 59 minutes";
+        assert!(matches!(detect_error(url, ban), Err(EhError::IpBanned(_))));
+        let removed = "<html><p>This gallery has been removed or is unavailable.</p></html>";
+        assert!(matches!(detect_error(url, removed), Err(EhError::GalleryRemoved(_))));
+        assert!(detect_error(url, "<html><h1 id=\"gn\">title</h1></html>").is_ok());
+        let title = "<html><head><title>Gallery Not Available - ExHentai.org</title></head></html>";
+        assert!(matches!(detect_error(url, title), Err(EhError::GalleryRemoved(_))));
+        // 评论里引用的提示不影响正常的画廊页面
+        let quoted = r#"<html><h1 id="gn">title</h1><div id="cdiv">
+            <div class="c6">You have exceeded your image viewing limits</div>
+            <div class="c6">This gallery has been removed or is unavailable.</div></div></html>"#;
+        assert!(detect_error(url, quoted).is_ok());
+        assert!(matches!(
+            detect_error(url, "You have exceeded your image viewing limits."),
+            Err(EhError::BandwidthExceeded)
+        ));
+    }
+
+    #[test]
+    fn parse_home_quota() {
+        let text = "<p>You are currently at <strong>1,234</strong> towards a limit of <strong>5,000</strong>.</p>";
+        let quota = parse_quota(text).unwrap();
+        assert_eq!(quota, EhQuota { used: 1234, limit: 5000 });
+        assert_eq!(quota.remaining(), 3766);
+        assert_eq!(parse_quota("<html></html>"), None);
+    }
+
+    #[test]
+    fn parse_newer_version_links() {
+        let html = Html::parse_document(
+            r#"<div id="gnd"><p>There are newer versions of this gallery available:</p>
+            <a href="https://exhentai.org/g/2549144/16b1b7bab1/">v2</a>, added 2023-05-01 12:00<br />
+            <a href="https://exhentai.org/g/2549145/16b1b7bab2/">v3</a>, added 2023-05-02 12:00</div>"#,
+        );
+        let versions = parse_newer_versions(&html);
+        assert_eq!(versions.iter().map(|u| u.id()).collect::<Vec<_>>(), [2549144, 2549145]);
+        assert!(parse_newer_versions(&Html::parse_document("<div id=\"gdd\"></div>")).is_empty());
+    }
+
+    #[test]
+    fn parse_comment_list() {
+        let html = Html::parse_document(
+            r#"<div id="cdiv" class="gm">
+            <div class="c1"><div class="c2"><div class="c3">Posted on 19 May 2023, 08:00 by: &nbsp; <a href="https://e-hentai.org/uploader/up">up</a>&nbsp; &nbsp; </div><div class="c4 nosel">Uploader Comment</div></div><div class="c6" id="comment_0">line 1<br>line 2</div></div>
+            <div class="c1"><div class="c2"><div class="c3">Posted on 20 May 2023, 10:11 by: &nbsp; <a href="https://e-hentai.org/uploader/reader">reader</a>&nbsp; &nbsp; </div><div class="c5 nosel"><span id="comment_score_123">-5</span></div></div><div class="c6" id="comment_123">see <a href="https://example.com">this</a> page</div></div>
+            </div>"#,
+        );
+        let comments = parse_comments(&html);
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments[0].id, 0);
+        assert_eq!(comments[0].author, "up");
+        assert_eq!(comments[0].score, 0);
+        assert_eq!(comments[0].content, "line 1\nline 2");
+        assert_eq!(comments[1].id, 123);
+        assert_eq!(comments[1].score, -5);
+        assert_eq!(comments[1].content, "see this page");
+        assert_eq!(comments[1].posted.to_string(), "2023-05-20 10:11:00");
+    }
+
+    #[test]
+    fn parse_language_row() {
+        let html = Html::parse_document(
+            r#"<div id="gdd"><table>
+            <tr><td class="gdt1">Posted:</td><td class="gdt2">2023-05-19 08:00</td></tr>
+            <tr><td class="gdt1">Language:</td><td class="gdt2">Chinese &nbsp;<span class="halp" title="This gallery has been translated from the original language text.">TR</span></td></tr>
+            </table></div>"#,
+        );
+        assert_eq!(parse_language(&html).as_deref(), Some("Chinese"));
+        assert_eq!(parse_language(&Html::parse_document("<div id=\"gdd\"></div>")), None);
+    }
+
+    #[test]
+    fn detect_509() {
+        assert!(check_image_url("https://exhentai.org/img/509.gif").is_err());
+        assert!(check_image_url("https://ehgt.org/g/509s.gif").is_err());
+        assert!(check_image_url("https://abc.hath.network/h/xxx/keystamp=1;fileindex=2;xres=org/01.jpg").is_ok());
+    }
+}
@@ -14,4 +14,26 @@ pub enum EhError {
     DateTimeError(#[from] chrono::format::ParseError),
     #[error("h@h url broken: {0}")]
     HaHUrlBroken(String),
+    #[error("api error: {0}")]
+    ApiError(String),
+    #[error("ip banned: {0}")]
+    IpBanned(String),
+    #[error("sad panda: cookie expired or no exhentai access")]
+    SadPanda,
+    #[error("509: image viewing limit exceeded")]
+    BandwidthExceeded,
+    #[error("gallery removed: {0}")]
+    GalleryRemoved(String),
+    #[error("no available account")]
+    NoAvailableAccount,
+}
+
+impl EhError {
+    /// 是否为账号或者 IP 层面的问题，此时继续请求只会让情况更糟，应该暂停扫描
+    pub fn is_blocked(&self) -> bool {
+        matches!(
+            self,
+            Self::IpBanned(_) | Self::SadPanda | Self::BandwidthExceeded | Self::NoAvailableAccount
+        )
+    }
 }
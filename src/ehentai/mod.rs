@@ -1,7 +1,9 @@
+mod account;
 mod client;
 mod error;
 mod types;
 
+pub use account::*;
 pub use client::*;
 pub use error::*;
 pub use types::*;
@@ -5,9 +5,25 @@ use chrono::prelude::*;
 use indexmap::IndexMap;
 use once_cell::sync::Lazy;
 use regex::Regex;
+use serde::{Deserialize, Deserializer};
 
 use super::error::EhError;
+use crate::config::EH_SITE;
 use crate::database::GalleryEntity;
+use crate::utils::html::unescape;
+
+/// 默认使用里站
+const DEFAULT_SITE: &str = "https://exhentai.org";
+
+/// 当前使用的站点地址，例如 https://exhentai.org，末尾不带斜杠
+pub fn site_url() -> &'static str {
+    EH_SITE.get().map_or(DEFAULT_SITE, |s| s.as_str())
+}
+
+/// 解析 URL 时接受表站、里站以及配置的站点
+fn is_known_site(site: &str) -> bool {
+    matches!(site, "https://e-hentai.org" | "https://exhentai.org") || site == site_url()
+}
 
 // 画廊地址，格式为 https://exhentai.org/g/2549143/16b1b7bab0/
 #[derive(Debug, Clone, PartialEq)]
@@ -20,7 +36,7 @@ pub struct EhGalleryUrl {
 impl EhGalleryUrl {
     /// 画廊 URL
     pub fn url(&self) -> String {
-        format!("https://exhentai.org/g/{}/{}/", self.id, self.token)
+        format!("{}/g/{}/{}/", site_url(), self.id, self.token)
     }
 
     /// 画廊 ID
@@ -44,10 +60,13 @@ impl FromStr for EhGalleryUrl {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         static RE: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"https://(?:e-hentai|exhentai)\.org/g/(?P<id>\d+)/(?P<token>[^/]+)/?(?P<cover>#\d+)?")
+            Regex::new(r"(?P<site>https?://[^/]+)/g/(?P<id>\d+)/(?P<token>[^/]+)/?(?P<cover>#\d+)?")
                 .unwrap()
         });
-        let captures = RE.captures(s).ok_or_else(|| EhError::InvalidURL(s.to_owned()))?;
+        let captures = RE
+            .captures(s)
+            .filter(|c| is_known_site(&c["site"]))
+            .ok_or_else(|| EhError::InvalidURL(s.to_owned()))?;
         // NOTE: 由于是正则匹配出来的结果，此处 unwrap 不会造成 panic
         let token = captures.name("token").unwrap().as_str().to_owned();
         let id = captures.name("id").and_then(|s| s.as_str().parse().ok()).unwrap();
@@ -77,11 +96,15 @@ impl EhPageUrl {
     pub fn url(&self) -> String {
         match &self.nl {
             None => {
-                format!("https://exhentai.org/s/{}/{}-{}", self.hash, self.gallery_id, self.page)
+                format!("{}/s/{}/{}-{}", site_url(), self.hash, self.gallery_id, self.page)
             }
             Some(nl) => format!(
-                "https://exhentai.org/s/{}/{}-{}?nl={}",
-                self.hash, self.gallery_id, self.page, nl
+                "{}/s/{}/{}-{}?nl={}",
+                site_url(),
+                self.hash,
+                self.gallery_id,
+                self.page,
+                nl
             ),
         }
     }
@@ -116,10 +139,13 @@ impl FromStr for EhPageUrl {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         static RE: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"https://(?:e-hentai|exhentai)\.org/s/(?P<hash>.+)/(?P<id>\d+)-(?P<page>\d+)").unwrap()
+            Regex::new(r"(?P<site>https?://[^/]+)/s/(?P<hash>.+)/(?P<id>\d+)-(?P<page>\d+)").unwrap()
         });
 
-        let captures = RE.captures(s).ok_or_else(|| EhError::InvalidURL(s.to_owned()))?;
+        let captures = RE
+            .captures(s)
+            .filter(|c| is_known_site(&c["site"]))
+            .ok_or_else(|| EhError::InvalidURL(s.to_owned()))?;
         // NOTE: 由于是正则匹配出来的结果，此处 unwrap 不会造成 panic
         let hash = captures.name("hash").unwrap().as_str().to_owned();
         let gallery_id = captures.name("id").and_then(|s| s.as_str().parse().ok()).unwrap();
@@ -135,6 +161,44 @@ impl Display for EhPageUrl {
     }
 }
 
+/// 页面中解析出的图片
+#[derive(Debug, Clone)]
+pub struct EhImage {
+    /// 图片的 fileindex
+    pub fileindex: u32,
+    /// 图片的实际地址
+    pub url: String,
+    /// 是否为原图，经过缩放的图片与页面 hash 对应不上
+    pub original: bool,
+    /// 页面上显示的缩放过的图片，原图下载失败时作为备选
+    pub preview: Option<String>,
+}
+
+/// 图片额度的使用情况，来自 home.php
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EhQuota {
+    /// 已使用的额度
+    pub used: u32,
+    /// 额度上限
+    pub limit: u32,
+}
+
+impl EhQuota {
+    /// 剩余的额度
+    pub fn remaining(&self) -> u32 {
+        self.limit.saturating_sub(self.used)
+    }
+}
+
+/// 多页查看器（MPV）的信息，用于通过 API 批量获取图片地址
+#[derive(Debug, Clone)]
+pub struct EhMpv {
+    /// 画廊 ID
+    pub gid: i32,
+    /// 调用 imagedispatch 时需要的 key
+    pub mpvkey: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct EhGallery {
     /// URL
@@ -155,6 +219,156 @@ pub struct EhGallery {
     pub posted: NaiveDateTime,
     /// 封面是第几张
     pub cover: usize,
+    /// 分类，例如 Doujinshi、Manga
+    pub category: String,
+    /// 语言，例如 Chinese，页面上没有显示时为空
+    pub language: Option<String>,
+    /// 上传者
+    pub uploader: String,
+    /// 所有图片的总大小，单位为字节
+    pub filesize: u64,
+    /// 平均评分
+    pub rating: f32,
+    /// 评分人数
+    pub rating_count: i32,
+    /// 画廊的新版本，最后一个为最新版本
+    pub newer_versions: Vec<EhGalleryUrl>,
+    /// 是否已被隐藏
+    pub expunged: bool,
+    /// 画廊评论
+    pub comments: Vec<EhComment>,
+}
+
+/// 画廊页面上的评论
+#[derive(Debug, Clone, PartialEq)]
+pub struct EhComment {
+    /// 评论 ID，上传者的评论为 0
+    pub id: i64,
+    /// 评论者
+    pub author: String,
+    /// 评论得分，上传者的评论没有得分，记为 0
+    pub score: i32,
+    /// 评论内容，换行会被保留
+    pub content: String,
+    /// 评论时间
+    pub posted: NaiveDateTime,
+}
+
+/// 画廊第一页中除了图片之外的信息，用于定期更新
+#[derive(Debug, Clone)]
+pub struct EhGalleryPage {
+    /// 画廊的新版本，最后一个为最新版本
+    pub newer_versions: Vec<EhGalleryUrl>,
+    /// 画廊评论
+    pub comments: Vec<EhComment>,
+    /// 评分人数
+    pub rating_count: Option<i32>,
+}
+
+/// 通过 api.php 的 gdata 方法获取的画廊元数据
+///
+/// 除了收藏数和页面列表，画廊页面上的信息基本都可以从这里拿到
+#[derive(Debug, Clone, Deserialize)]
+pub struct EhGalleryMeta {
+    /// 画廊 ID
+    pub gid: i32,
+    /// 画廊 token
+    pub token: String,
+    /// 画廊标题
+    #[serde(deserialize_with = "deserialize_html")]
+    pub title: String,
+    /// 画廊日文标题，没有时为空字符串
+    #[serde(deserialize_with = "deserialize_html")]
+    pub title_jpn: String,
+    /// 分类，例如 Doujinshi、Manga
+    pub category: String,
+    /// 上传者
+    pub uploader: String,
+    /// 发布时间，unix 时间戳
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub posted: i64,
+    /// 页数
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub filecount: usize,
+    /// 所有图片的总大小，单位为字节
+    #[serde(default)]
+    pub filesize: u64,
+    /// 平均评分
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub rating: f32,
+    /// 是否已被隐藏
+    #[serde(default)]
+    pub expunged: bool,
+    /// 父画廊 ID
+    pub parent_gid: Option<String>,
+    /// 父画廊 token
+    pub parent_key: Option<String>,
+    /// 最新版本的画廊 ID，没有更新过的画廊与 gid 相同
+    #[serde(default)]
+    pub current_gid: Option<String>,
+    /// 画廊标签，格式为 namespace:tag，misc 标签没有 namespace
+    pub tags: Vec<String>,
+}
+
+impl EhGalleryMeta {
+    pub fn url(&self) -> EhGalleryUrl {
+        EhGalleryUrl { id: self.gid, token: self.token.clone(), cover: 0 }
+    }
+
+    pub fn title_jp(&self) -> Option<String> {
+        Some(self.title_jpn.clone()).filter(|s| !s.is_empty())
+    }
+
+    pub fn parent(&self) -> Option<EhGalleryUrl> {
+        let id = self.parent_gid.as_ref()?.parse().ok()?;
+        Some(EhGalleryUrl { id, token: self.parent_key.clone()?, cover: 0 })
+    }
+
+    /// 是否有更新的版本，需要从画廊页面获取新版本的地址
+    pub fn has_newer_version(&self) -> bool {
+        self.current_gid.as_ref().and_then(|s| s.parse::<i32>().ok()).is_some_and(|id| id != self.gid)
+    }
+
+    pub fn posted(&self) -> NaiveDateTime {
+        DateTime::from_timestamp(self.posted, 0).unwrap_or_default().naive_utc()
+    }
+
+    /// 从 language 标签中获取语言，格式和画廊页面上的一致，例如 Chinese
+    pub fn language(&self) -> Option<String> {
+        // 这几个标签表示翻译方式，不是语言
+        const NOT_LANGUAGE: [&str; 4] = ["translated", "rewrite", "speechless", "text cleaned"];
+        let language = self
+            .tags
+            .iter()
+            .filter_map(|tag| tag.strip_prefix("language:"))
+            .find(|tag| !NOT_LANGUAGE.contains(tag))?;
+        let mut chars = language.chars();
+        Some(chars.next()?.to_uppercase().chain(chars).collect())
+    }
+
+    /// 按 namespace 整理标签，和画廊页面上的格式保持一致
+    pub fn tags(&self) -> IndexMap<String, Vec<String>> {
+        let mut tags = IndexMap::<String, Vec<String>>::new();
+        for tag in &self.tags {
+            let (ns, tag) = tag.split_once(':').unwrap_or(("other", tag));
+            tags.entry(ns.to_string()).or_default().push(tag.to_string());
+        }
+        tags
+    }
+}
+
+fn deserialize_html<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    String::deserialize(deserializer).map(|s| unescape(&s))
+}
+
+/// API 中的数字大多是以字符串的形式返回的
+fn deserialize_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
 }
 
 pub trait GalleryInfo {
@@ -169,6 +383,19 @@ pub trait GalleryInfo {
     fn pages(&self) -> usize;
 
     fn cover(&self) -> usize;
+
+    fn category(&self) -> Option<&str>;
+}
+
+impl EhGallery {
+    /// 画廊第一页中除了图片之外的信息，与 get_gallery_page 的结果相同
+    pub fn page_info(&self) -> EhGalleryPage {
+        EhGalleryPage {
+            newer_versions: self.newer_versions.clone(),
+            comments: self.comments.clone(),
+            rating_count: Some(self.rating_count),
+        }
+    }
 }
 
 impl GalleryInfo for EhGallery {
@@ -195,6 +422,10 @@ impl GalleryInfo for EhGallery {
     fn cover(&self) -> usize {
         self.cover
     }
+
+    fn category(&self) -> Option<&str> {
+        Some(&self.category)
+    }
 }
 
 impl GalleryInfo for GalleryEntity {
@@ -221,6 +452,10 @@ impl GalleryInfo for GalleryEntity {
     fn cover(&self) -> usize {
         0
     }
+
+    fn category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
 }
 
 #[cfg(test)]
@@ -246,6 +481,12 @@ mod tests {
         assert_eq!(url.url(), "https://exhentai.org/g/2423705/3962191348/");
     }
 
+    #[test]
+    fn reject_unknown_site() {
+        assert!("https://example.com/g/2423705/3962191348/".parse::<EhGalleryUrl>().is_err());
+        assert!("https://example.com/s/03af734602/1932743-1".parse::<EhPageUrl>().is_err());
+    }
+
     #[test]
     fn test_actual_urls() {
         let url1 = "https://e-hentai.org/g/2988976/d7f6178199/";
@@ -261,6 +502,32 @@ mod tests {
         assert!(parsed2.is_ok());
     }
 
+    #[test]
+    fn parse_gallery_meta() {
+        let json = r#"{
+            "gid": 2549143, "token": "16b1b7bab0", "archiver_key": "x",
+            "title": "[Artist] Title &amp; More", "title_jpn": "",
+            "category": "Manga", "thumb": "", "uploader": "someone",
+            "posted": "1684483200", "filecount": "20", "filesize": 1234,
+            "expunged": false, "rating": "4.56", "torrentcount": "0",
+            "torrents": [], "parent_gid": "2549000", "parent_key": "abcdef0123",
+            "current_gid": "2549200", "current_key": "abcdef0124",
+            "tags": ["language:chinese", "language:translated", "female:lolicon", "full color"]
+        }"#;
+        let meta = serde_json::from_str::<EhGalleryMeta>(json).unwrap();
+        assert_eq!(meta.title, "[Artist] Title & More");
+        assert_eq!(meta.title_jp(), None);
+        assert_eq!(meta.filecount, 20);
+        assert_eq!(meta.filesize, 1234);
+        assert_eq!(meta.posted().to_string(), "2023-05-19 08:00:00");
+        assert_eq!(meta.parent().unwrap().url(), "https://exhentai.org/g/2549000/abcdef0123/");
+        assert!(meta.has_newer_version());
+        assert_eq!(meta.language().as_deref(), Some("Chinese"));
+        let tags = meta.tags();
+        assert_eq!(tags["language"], vec!["chinese", "translated"]);
+        assert_eq!(tags["other"], vec!["full color"]);
+    }
+
     #[test]
     fn parse_page_url() {
         let s = "https://exhentai.org/s/03af734602/1932743-1";
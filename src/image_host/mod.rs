@@ -0,0 +1,61 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use futures::future::BoxFuture;
+
+use crate::config::{Config, ImageHostKind};
+use crate::utils::http::{ClientKind, HttpFactory};
+
+mod s3;
+mod teletype;
+
+pub use s3::S3Host;
+pub use teletype::TeletypeHost;
+
+/// 图床后端，负责把图片上传到某个公开可访问的位置，并返回其 URL
+pub trait ImageHost: Debug + Send + Sync {
+    /// 图床名称，用于日志
+    fn name(&self) -> &'static str;
+
+    /// 上传一张图片，返回可以直接在 telegraph 中引用的 URL
+    fn upload<'a>(&'a self, name: &'a str, data: &'a [u8]) -> BoxFuture<'a, Result<String>>;
+}
+
+/// 根据配置文件创建所有图床，第一个为主图床
+pub fn from_config(config: &Config) -> Result<Vec<Arc<dyn ImageHost>>> {
+    if config.image_hosts.is_empty() {
+        bail!("至少需要配置一个图床");
+    }
+    let http = HttpFactory::new(config);
+    let mut hosts = Vec::<Arc<dyn ImageHost>>::new();
+    for kind in &config.image_hosts {
+        let host: Arc<dyn ImageHost> = match kind {
+            ImageHostKind::Teletype => {
+                let teletype = config.teletype.as_ref().context("缺少 [teletype] 配置")?;
+                Arc::new(TeletypeHost::new(teletype.token.clone(), http.client(ClientKind::Other)?))
+            }
+            ImageHostKind::S3 => {
+                let s3 = config.s3.as_ref().context("缺少 [s3] 配置")?;
+                Arc::new(S3Host::new(s3)?)
+            }
+        };
+        if hosts.iter().any(|h| h.name() == host.name()) {
+            bail!("图床 {} 重复配置", host.name());
+        }
+        hosts.push(host);
+    }
+    Ok(hosts)
+}
+
+/// 根据文件后缀推断图片的 Content-Type
+fn content_type(name: &str) -> &'static str {
+    let ext = name.rsplit('.').next().unwrap_or("jpg");
+    match ext.to_lowercase().as_str() {
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        _ => "image/jpeg",
+    }
+}
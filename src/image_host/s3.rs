@@ -0,0 +1,58 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+use tracing::debug;
+
+use super::{content_type, ImageHost};
+use crate::config::S3;
+
+/// 上传到 S3 兼容的对象存储，通过桶绑定的域名公开访问
+///
+/// NOTE: rust-s3 使用自己的 HTTP 客户端，不会经过配置文件中的代理
+#[derive(Debug, Clone)]
+pub struct S3Host {
+    bucket: Box<Bucket>,
+    host: String,
+}
+
+impl S3Host {
+    pub fn new(config: &S3) -> Result<Self> {
+        let region =
+            Region::Custom { region: config.region.clone(), endpoint: config.endpoint.clone() };
+        let credentials = Credentials {
+            access_key: Some(config.access_key.clone()),
+            secret_key: Some(config.secret_key.clone()),
+            security_token: None,
+            session_token: None,
+            expiration: None,
+        };
+        let bucket = Bucket::new(&config.bucket, region, credentials)?.with_path_style();
+        let host = config.host.trim_end_matches('/').to_string();
+        Ok(Self { bucket, host })
+    }
+
+    async fn upload_inner(&self, name: &str, data: &[u8]) -> Result<String> {
+        debug!("正在上传到 S3: 文件名: {}, 大小: {} 字节", name, data.len());
+        // NOTE: 由于启用了 fail-on-err，非 2xx 的响应会直接返回错误
+        self.bucket.put_object_with_content_type(name, data, content_type(name)).await?;
+        let url = if self.host.starts_with("http") {
+            format!("{}/{}", self.host, name)
+        } else {
+            format!("https://{}/{}", self.host, name)
+        };
+        debug!("S3 上传成功 ({}): {}", name, url);
+        Ok(url)
+    }
+}
+
+impl ImageHost for S3Host {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    fn upload<'a>(&'a self, name: &'a str, data: &'a [u8]) -> BoxFuture<'a, Result<String>> {
+        self.upload_inner(name, data).boxed()
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use reqwest::Client;
+use tracing::{debug, error};
+
+use super::{content_type, ImageHost};
+
+/// 上传到 teletype.in
+#[derive(Debug, Clone)]
+pub struct TeletypeHost {
+    client: Client,
+    token: String,
+}
+
+impl TeletypeHost {
+    pub fn new(token: String, client: Client) -> Self {
+        Self { client, token }
+    }
+
+    async fn upload_inner(&self, name: &str, data: &[u8]) -> Result<String> {
+        debug!("正在上传到teletype.in: 文件名: {}, 大小: {} 字节", name, data.len());
+
+        let part = reqwest::multipart::Part::bytes(data.to_vec())
+            .file_name(name.to_string())
+            .mime_str(content_type(name))?;
+
+        let form = reqwest::multipart::Form::new().part("file", part).text("type", "images");
+
+        let response = self
+            .client
+            .put("https://teletype.in/media/") // 一定要添加尾部斜杠
+            .header("Authorization", &self.token)
+            .multipart(form)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text =
+                response.text().await.unwrap_or_else(|_| "无法获取错误详情".to_string());
+            error!("Teletype上传失败: 状态码: {}, 错误信息: {}", status, error_text);
+
+            if status.as_u16() == 401 || status.as_u16() == 403 {
+                return Err(anyhow!("Teletype授权失败，请检查令牌: {} - {}", status, error_text));
+            }
+            return Err(anyhow!("上传到teletype.in失败: {} - {}", status, error_text));
+        }
+
+        let response_text = response.text().await?;
+        debug!("Teletype上传成功 ({}): 响应: {}", name, response_text);
+
+        // 解析JSON响应
+        let url = match serde_json::from_str::<serde_json::Value>(&response_text) {
+            Ok(json) => {
+                // 从JSON中获取url字段
+                if let Some(url) = json["url"].as_str() {
+                    url.to_string()
+                } else {
+                    return Err(anyhow!("无法从JSON响应中提取URL字段: {}", response_text));
+                }
+            }
+            Err(e) => {
+                // 如果不是有效的JSON，尝试直接使用响应文本作为URL
+                debug!("无法解析JSON响应，尝试直接使用响应文本: {}", e);
+                let url_text = response_text.trim();
+                if url_text.starts_with("http") {
+                    url_text.to_string()
+                } else {
+                    return Err(anyhow!(
+                        "无效的响应内容，既不是URL也不是有效JSON: {}",
+                        response_text
+                    ));
+                }
+            }
+        };
+
+        debug!("提取的URL ({}): {}", name, url);
+
+        if url.is_empty() || !url.starts_with("http") {
+            return Err(anyhow!("提取的URL无效: {}", url));
+        }
+
+        Ok(url)
+    }
+}
+
+impl ImageHost for TeletypeHost {
+    fn name(&self) -> &'static str {
+        "teletype"
+    }
+
+    fn upload<'a>(&'a self, name: &'a str, data: &'a [u8]) -> BoxFuture<'a, Result<String>> {
+        self.upload_inner(name, data).boxed()
+    }
+}
@@ -3,7 +3,7 @@ pub mod config;
 pub mod database;
 pub mod daemon;
 pub mod ehentai;
-pub mod teletype_uploader;
+pub mod image_host;
 pub mod tags;
 pub mod uploader;
 pub mod utils;
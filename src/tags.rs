@@ -8,9 +8,12 @@ use serde::Deserialize;
 use tokio::time::{self, Duration};
 use tracing::{error, info};
 
+use crate::utils::http::{ClientKind, HttpFactory};
+
 #[derive(Debug, Clone)]
 pub struct EhTagTransDB {
     file: String,
+    http: HttpFactory,
     db: Arc<RwLock<Option<EhTagTransDBInner>>>,
 }
 
@@ -38,7 +41,7 @@ struct TagInfo {
 }
 
 impl EhTagTransDB {
-    pub fn new(file: &str) -> Self {
+    pub fn new(file: &str, http: HttpFactory) -> Self {
         let db = match fs::read_to_string(file) {
             Ok(text) => {
                 match serde_json::from_str(&text) {
@@ -57,7 +60,7 @@ impl EhTagTransDB {
                 None
             }
         };
-        Self { file: file.to_string(), db: Arc::new(RwLock::new(db)) }
+        Self { file: file.to_string(), http, db: Arc::new(RwLock::new(db)) }
     }
 
     pub async fn start(&self) {
@@ -72,8 +75,8 @@ impl EhTagTransDB {
 
     async fn update(&self) -> Result<()> {
         info!("更新 tag 中……");
-        // 此处得设置 user-agent，否则 github 会 403
-        let client = reqwest::Client::builder().user_agent("exloli").build()?;
+        // NOTE: github 要求设置 user-agent，否则会 403，HttpFactory 已经设置过了
+        let client = self.http.client(ClientKind::Other)?;
         let resp = client
             .get("https://api.github.com/repos/EhTagTranslation/Database/releases/latest")
             .send()
@@ -157,11 +160,11 @@ impl EhTagTransDB {
 
 #[cfg(test)]
 mod test {
-    use super::EhTagTransDB;
+    use super::{EhTagTransDB, HttpFactory};
 
     #[test]
     fn test() {
-        let db = EhTagTransDB::new("./db.text.json");
+        let db = EhTagTransDB::new("./db.text.json", HttpFactory::default());
         assert_eq!(db.trans_namespace("female"), "女性");
         assert_eq!(db.trans("female", "lolicon"), vec!["萝莉"]);
         assert_eq!(db.trans("character", "yui"), vec!["由依", "结衣"]);
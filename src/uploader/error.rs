@@ -0,0 +1,210 @@
+use thiserror::Error;
+
+use crate::ehentai::EhError;
+
+/// 上传画廊过程中可能出现的错误
+///
+/// 涉及具体页面的错误会携带页码，`retryable` 表示下次扫描时是否值得再次尝试
+#[derive(Debug, Error)]
+pub enum UploadError {
+    #[error("获取画廊信息失败：{0}")]
+    Gallery(#[from] EhError),
+    #[error("解析第 {page} 页失败：{source}")]
+    PageResolve { page: i32, retryable: bool, source: EhError },
+    #[error("下载第 {page} 页失败：{source}")]
+    Download { page: i32, retryable: bool, source: anyhow::Error },
+    #[error("图床拒绝了第 {page} 页：{source}")]
+    HostRejected { page: i32, retryable: bool, source: anyhow::Error },
+    #[error("数据库操作失败：{source}")]
+    Database { page: Option<i32>, source: sqlx::Error },
+    #[error("Telegram 请求失败：{0}")]
+    Telegram(#[from] teloxide::RequestError),
+    #[error("发布 telegraph 文章失败：{0}")]
+    Telegraph(anyhow::Error),
+    #[error("创建 HTTP 客户端失败：{0}")]
+    Config(anyhow::Error),
+    #[error("上传任务异常退出：{0}")]
+    Aborted(#[from] tokio::task::JoinError),
+    /// 剩余的图片额度不够上传整个画廊，需要的额度超过上限时永远无法自动上传
+    #[error("图片额度不足：需要 {need}，剩余 {remaining}，上限 {limit}")]
+    Postponed { need: u32, remaining: u32, limit: u32 },
+    /// 同一个上传任务有多个调用者时，其余调用者只能拿到错误信息
+    #[error("上传任务失败：{0}")]
+    Job(String),
+}
+
+impl From<sqlx::Error> for UploadError {
+    fn from(source: sqlx::Error) -> Self {
+        Self::Database { page: None, source }
+    }
+}
+
+impl UploadError {
+    /// 下载失败，如果原始错误已经是 UploadError 则原样返回，避免重复包装
+    pub fn download(page: i32, err: anyhow::Error) -> Self {
+        match err.downcast::<Self>() {
+            Ok(e) => e,
+            Err(source) => Self::Download { page, retryable: true, source },
+        }
+    }
+
+    /// 出错的页码，与具体页面无关的错误返回 None
+    pub fn page(&self) -> Option<i32> {
+        match self {
+            Self::PageResolve { page, .. }
+            | Self::Download { page, .. }
+            | Self::HostRejected { page, .. } => Some(*page),
+            Self::Database { page, .. } => *page,
+            _ => None,
+        }
+    }
+
+    /// 是否为临时性错误，下次扫描时可以再次尝试
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::PageResolve { retryable, .. }
+            | Self::Download { retryable, .. }
+            | Self::HostRejected { retryable, .. } => *retryable,
+            Self::Telegram(e) => matches!(
+                e,
+                teloxide::RequestError::Network(_)
+                    | teloxide::RequestError::RetryAfter(_)
+                    | teloxide::RequestError::Io(_)
+            ),
+            Self::Gallery(e) => !matches!(e, EhError::GalleryRemoved(_)),
+            Self::Telegraph(_) | Self::Job(_) => true,
+            Self::Database { source, .. } => is_transient_db_error(source),
+            Self::Aborted(e) => e.is_cancelled(),
+            Self::Config(_) => false,
+            Self::Postponed { need, limit, .. } => need <= limit,
+        }
+    }
+
+    /// 被 E 站封禁或者额度用完导致的错误，与画廊本身无关
+    pub fn blocked(&self) -> Option<&EhError> {
+        let err = match self {
+            Self::Gallery(e) | Self::PageResolve { source: e, .. } => Some(e),
+            Self::Download { source, .. } => source.downcast_ref::<EhError>(),
+            _ => None,
+        };
+        err.filter(|e| e.is_blocked())
+    }
+
+    /// 判断一个 anyhow 错误是否值得重试，被封禁时不再重试，其余非 UploadError 的错误一律视为可重试
+    pub fn is_retryable_anyhow(err: &anyhow::Error) -> bool {
+        if let Some(e) = err.downcast_ref::<EhError>() {
+            return !e.is_blocked();
+        }
+        err.downcast_ref::<Self>().is_none_or(Self::is_retryable)
+    }
+}
+
+/// 连接池超时、数据库被锁等临时性错误，约束冲突等逻辑错误重试也没有意义
+fn is_transient_db_error(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) => true,
+        // SQLite 的扩展错误码低 8 位为主错误码，5 为 SQLITE_BUSY，6 为 SQLITE_LOCKED
+        sqlx::Error::Database(e) => e
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .is_some_and(|code| matches!(code & 0xff, 5 | 6)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::fmt;
+
+    use anyhow::anyhow;
+    use sqlx::error::{DatabaseError, ErrorKind};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct SqliteError(i32);
+
+    impl fmt::Display for SqliteError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "sqlite error {}", self.0)
+        }
+    }
+
+    impl std::error::Error for SqliteError {}
+
+    impl DatabaseError for SqliteError {
+        fn message(&self) -> &str {
+            "sqlite error"
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(self.0.to_string().into())
+        }
+
+        fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> ErrorKind {
+            match self.0 {
+                2067 => ErrorKind::UniqueViolation,
+                _ => ErrorKind::Other,
+            }
+        }
+    }
+
+    #[test]
+    fn download_error_not_nested() {
+        let inner: anyhow::Error =
+            UploadError::Download { page: 3, retryable: false, source: anyhow!("html") }.into();
+        assert!(!UploadError::is_retryable_anyhow(&inner));
+        let err = UploadError::download(5, inner);
+        assert_eq!(err.page(), Some(3));
+        assert!(!err.is_retryable());
+
+        let err = UploadError::download(5, anyhow!("timeout"));
+        assert_eq!(err.page(), Some(5));
+        assert!(err.is_retryable());
+    }
+
+    #[test]
+    fn blocked_error() {
+        let err = UploadError::download(5, EhError::BandwidthExceeded.into());
+        assert!(err.blocked().is_some());
+        assert!(!UploadError::is_retryable_anyhow(&EhError::BandwidthExceeded.into()));
+        let err = UploadError::Gallery(EhError::GalleryRemoved("x".into()));
+        assert!(err.blocked().is_none());
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn database_error_retryable() {
+        let db = |code| UploadError::from(sqlx::Error::Database(Box::new(SqliteError(code))));
+        assert!(UploadError::from(sqlx::Error::PoolTimedOut).is_retryable());
+        // SQLITE_BUSY、SQLITE_LOCKED 以及 SQLITE_BUSY_SNAPSHOT
+        assert!(db(5).is_retryable());
+        assert!(db(6).is_retryable());
+        assert!(db(517).is_retryable());
+        // SQLITE_CONSTRAINT_UNIQUE
+        assert!(!db(2067).is_retryable());
+        assert!(!UploadError::from(sqlx::Error::RowNotFound).is_retryable());
+    }
+
+    #[test]
+    fn postponed_retryable() {
+        let err = UploadError::Postponed { need: 300, remaining: 100, limit: 5000 };
+        assert!(err.is_retryable());
+        assert!(err.blocked().is_none());
+        let err = UploadError::Postponed { need: 6000, remaining: 5000, limit: 5000 };
+        assert!(!err.is_retryable());
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::Result;
+use chrono::Utc;
+use regex::Regex;
+
+use crate::config::Filter;
+use crate::ehentai::EhGallery;
+
+/// 自动上传前的内容过滤，规则来自配置文件中的 [filter]
+#[derive(Debug, Clone)]
+pub struct ContentFilter {
+    config: Filter,
+    title_blacklist: Vec<Regex>,
+    title_whitelist: Vec<Regex>,
+}
+
+impl ContentFilter {
+    pub fn new(config: &Filter) -> Result<Self> {
+        let compile = |v: &[String]| v.iter().map(|s| Regex::new(s)).collect::<Result<Vec<_>, _>>();
+        Ok(Self {
+            config: config.clone(),
+            title_blacklist: compile(&config.title_blacklist)?,
+            title_whitelist: compile(&config.title_whitelist)?,
+        })
+    }
+
+    /// 检查画廊是否符合过滤规则，不符合时返回原因
+    pub fn reject_reason(&self, gallery: &EhGallery) -> Option<String> {
+        let config = &self.config;
+
+        for (ns, tags) in &config.tag_blacklist {
+            let hit = gallery.tags.get(ns).and_then(|v| v.iter().find(|t| tags.contains(t)));
+            if let Some(tag) = hit {
+                return Some(format!("含有黑名单标签 {}:{}", ns, tag));
+            }
+        }
+        for (ns, tags) in &config.tag_whitelist {
+            let hit = gallery.tags.get(ns).is_some_and(|v| v.iter().any(|t| tags.contains(t)));
+            if !hit {
+                return Some(format!("不含白名单中的 {} 标签", ns));
+            }
+        }
+
+        let pages = gallery.pages.len();
+        if config.min_pages.is_some_and(|min| pages < min) {
+            return Some(format!("页数过少：{}", pages));
+        }
+        if config.max_pages.is_some_and(|max| pages > max) {
+            return Some(format!("页数过多：{}", pages));
+        }
+        if config.min_favorite.is_some_and(|min| gallery.favorite < min) {
+            return Some(format!("收藏数过少：{}", gallery.favorite));
+        }
+        if config.min_rating.is_some_and(|min| gallery.rating < min) {
+            return Some(format!("评分过低：{}", gallery.rating));
+        }
+        if !config.categories.is_empty()
+            && !config.categories.iter().any(|c| c.eq_ignore_ascii_case(&gallery.category))
+        {
+            return Some(format!("分类不在允许范围内：{}", gallery.category));
+        }
+        if !config.languages.is_empty() {
+            let language = gallery.language.as_deref().unwrap_or_default();
+            if !config.languages.iter().any(|l| l.eq_ignore_ascii_case(language)) {
+                return Some(format!("语言不在允许范围内：{}", language));
+            }
+        }
+        if let Some(max_age) = config.max_age {
+            let age = Utc::now().naive_utc() - gallery.posted;
+            if age.to_std().is_ok_and(|age| age > max_age) {
+                return Some(format!("发布时间过早：{}", gallery.posted));
+            }
+        }
+
+        let titles = [Some(&gallery.title), gallery.title_jp.as_ref()];
+        let titles = titles.iter().flatten().collect::<Vec<_>>();
+        if let Some(re) =
+            self.title_blacklist.iter().find(|re| titles.iter().any(|t| re.is_match(t)))
+        {
+            return Some(format!("标题匹配黑名单 {}", re));
+        }
+        if !self.title_whitelist.is_empty()
+            && !self.title_whitelist.iter().any(|re| titles.iter().any(|t| re.is_match(t)))
+        {
+            return Some("标题不匹配白名单".to_string());
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use indexmap::IndexMap;
+
+    use super::*;
+
+    fn gallery() -> EhGallery {
+        let mut tags = IndexMap::new();
+        tags.insert("language".to_string(), vec!["chinese".to_string(), "translated".to_string()]);
+        tags.insert("female".to_string(), vec!["lolicon".to_string()]);
+        EhGallery {
+            url: "https://exhentai.org/g/2549143/16b1b7bab0/".parse().unwrap(),
+            title: "[Artist] Title (Comic LO 2023-01) [Chinese]".to_string(),
+            title_jp: None,
+            tags,
+            favorite: 100,
+            parent: None,
+            pages: vec!["https://exhentai.org/s/03af734602/2549143-1".parse().unwrap(); 20],
+            posted: Utc::now().naive_utc() - Duration::days(3),
+            cover: 0,
+            category: "Manga".to_string(),
+            language: Some("Chinese".to_string()),
+            uploader: "someone".to_string(),
+            filesize: 20 << 20,
+            rating: 4.5,
+            rating_count: 30,
+            newer_versions: vec![],
+            expunged: false,
+            comments: vec![],
+        }
+    }
+
+    fn filter(config: Filter) -> ContentFilter {
+        ContentFilter::new(&config).unwrap()
+    }
+
+    #[test]
+    fn filter_tags() {
+        let g = gallery();
+        let mut config = Filter::default();
+        config.tag_blacklist.insert("female".into(), vec!["netorare".into()]);
+        config.tag_whitelist.insert("language".into(), vec!["chinese".into()]);
+        assert_eq!(filter(config.clone()).reject_reason(&g), None);
+
+        config.tag_blacklist.insert("language".into(), vec!["translated".into()]);
+        assert!(filter(config.clone()).reject_reason(&g).is_some());
+
+        let mut config = Filter::default();
+        config.tag_whitelist.insert("language".into(), vec!["japanese".into()]);
+        assert!(filter(config).reject_reason(&g).is_some());
+    }
+
+    #[test]
+    fn filter_numbers() {
+        let g = gallery();
+        assert!(filter(Filter { min_pages: Some(21), ..Default::default() })
+            .reject_reason(&g)
+            .is_some());
+        assert!(filter(Filter { max_pages: Some(19), ..Default::default() })
+            .reject_reason(&g)
+            .is_some());
+        assert!(filter(Filter { min_favorite: Some(101), ..Default::default() })
+            .reject_reason(&g)
+            .is_some());
+        assert!(filter(Filter { min_rating: Some(4.6), ..Default::default() })
+            .reject_reason(&g)
+            .is_some());
+        let max_age = Some(std::time::Duration::from_secs(86400));
+        assert!(filter(Filter { max_age, ..Default::default() }).reject_reason(&g).is_some());
+        let max_age = Some(std::time::Duration::from_secs(86400 * 7));
+        assert_eq!(filter(Filter { max_age, ..Default::default() }).reject_reason(&g), None);
+    }
+
+    #[test]
+    fn filter_category_language() {
+        let g = gallery();
+        let config = Filter {
+            categories: vec!["doujinshi".into(), "manga".into()],
+            languages: vec!["chinese".into()],
+            ..Default::default()
+        };
+        assert_eq!(filter(config).reject_reason(&g), None);
+        let config = Filter { categories: vec!["Doujinshi".into()], ..Default::default() };
+        assert!(filter(config).reject_reason(&g).is_some());
+        let config = Filter { languages: vec!["Japanese".into()], ..Default::default() };
+        assert!(filter(config).reject_reason(&g).is_some());
+    }
+
+    #[test]
+    fn filter_title() {
+        let g = gallery();
+        let config = Filter { title_blacklist: vec![r"(?i)comic lo".into()], ..Default::default() };
+        assert!(filter(config).reject_reason(&g).is_some());
+        let config = Filter { title_whitelist: vec![r"\[Chinese\]".into()], ..Default::default() };
+        assert_eq!(filter(config).reject_reason(&g), None);
+        let config = Filter { title_whitelist: vec!["無修正".into()], ..Default::default() };
+        assert!(filter(config).reject_reason(&g).is_some());
+        assert!(ContentFilter::new(&Filter {
+            title_blacklist: vec!["(".into()],
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,2210 @@
+use std::backtrace::Backtrace;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{NaiveDateTime, Utc};
+use futures::{future, StreamExt, FutureExt};
+use regex::Regex;
+use reqwest::{Client, StatusCode};
+use telegraph_rs::{html_to_node, Telegraph};
+use teloxide::prelude::*;
+use teloxide::types::MessageId;
+use teloxide::utils::html::{code_inline, link};
+use tokio::task::JoinHandle;
+use tokio::time;
+use tracing::{debug, error, info, warn, Instrument};
+
+use crate::bot::Bot;
+use crate::config::{Config, MissingPagePolicy, PartialPublish, Profile};
+use crate::database::{
+    CommentEntity, GalleryEntity, GalleryFailureEntity, ImageEntity, ImageFlagEntity, ImageFlagKind,
+    ImageMirrorEntity, MessageEntity, MissingPageEntity, PageEntity, PollEntity, ScanStateEntity,
+    TelegraphEntity, UploadJobEntity, UploadJobState,
+};
+use crate::ehentai::{
+    self, AccountHealth, EhClient, EhError, EhGallery, EhGalleryMeta, EhGalleryPage, EhGalleryUrl, EhImage, EhPageUrl, EhQuota,
+    GalleryInfo,
+};
+use crate::image_host::{self, ImageHost};
+use crate::tags::EhTagTransDB;
+use crate::utils::http::{ClientKind, HttpFactory};
+use crate::utils::{self, pad_left};
+
+mod error;
+mod filter;
+mod queue;
+
+pub use self::error::UploadError;
+use self::filter::ContentFilter;
+use self::queue::{ProgressCallback, UploadQueue};
+
+/// 改进的重试机制，针对网络错误提供更多重试次数
+async fn retry_network_operation<T, F, Fut>(
+    operation_name: &str, 
+    func: F,
+    cancelled: Option<Arc<AtomicBool>>,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    retry_network_operation_with_limit(operation_name, 7, func, cancelled).await
+}
+
+/// 带上限参数的网络重试封装，便于在敏感操作上缩短失败等待时间
+async fn retry_network_operation_with_limit<T, F, Fut>(
+    operation_name: &str,
+    max_retries: usize,
+    mut func: F,
+    cancelled: Option<Arc<AtomicBool>>,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    const MAX_RETRIES: usize = 10; // 允许更多重试次数，减少因临时网络问题导致的画廊跳过
+
+    let max_retries = max_retries.clamp(1, MAX_RETRIES);
+
+    for attempt in 1..=max_retries {
+        // 在开始操作前检查取消状态
+        if let Some(ref c) = cancelled {
+            if c.load(Ordering::Relaxed) {
+                warn!("{} 操作被取消，停止执行", operation_name);
+                // 这里我们无法构造E，只能假设它是anyhow::Error或者返回上一轮的错误
+                // 但由于这是新的一轮，我们没有错误可返回。
+                // 这是一个泛型函数，E必须实现Display。
+                // 我们只能panic或者让func执行一次？
+                // 为了简单起见，我们在sleep期间检查取消。
+                // 如果在这里检测到取消，我们还是执行func吧，或者直接break循环？
+                // 如果break循环，我们需要返回一个Result。
+                // 我们无法构造 Err(E)。
+                // 所以我们只能继续执行，或者依靠sleep期间的检查。
+            }
+        }
+
+        match func().await {
+            Ok(result) => {
+                if attempt > 1 {
+                    info!("{} 在第 {} 次尝试后成功", operation_name, attempt);
+                }
+                return Ok(result);
+            }
+            Err(err) => {
+                // 不可重试的错误（比如下载到的不是图片）立刻返回
+                if !UploadError::is_retryable_anyhow(&err) {
+                    return Err(err);
+                }
+                if attempt >= max_retries {
+                    error!("{} 重试 {} 次后仍失败: {}", operation_name, max_retries, err);
+                    return Err(err);
+                }
+
+                // 更保守的退避策略：前几次快速重试，后面延迟增加
+                let delay = if attempt <= 3 {
+                    Duration::from_secs(attempt as u64) // 1s, 2s, 3s
+                } else {
+                    Duration::from_secs(5 + ((attempt - 3) * 5) as u64) // 10s, 15s, 20s, 25s
+                };
+
+                warn!("{} 失败 (尝试 {}/{}): {}, {}秒后重试",
+                      operation_name, attempt, max_retries, err, delay.as_secs());
+                
+                // 带取消检查的等待
+                if let Some(ref c) = cancelled {
+                    let step = Duration::from_millis(200);
+                    let mut left = delay;
+                    let mut is_cancelled = false;
+                    while left > Duration::ZERO {
+                         if c.load(Ordering::Relaxed) {
+                             is_cancelled = true;
+                             break;
+                         }
+                         let s = std::cmp::min(step, left);
+                         time::sleep(s).await;
+                         left -= s;
+                    }
+                    if is_cancelled {
+                        warn!("{} 操作被取消，停止重试", operation_name);
+                        return Err(err);
+                    }
+                } else {
+                    time::sleep(delay).await;
+                }
+            }
+        }
+    }
+    
+    unreachable!()
+}
+
+/// 将图片上传到所有图床，返回上传成功的 (图床名称, URL) 列表，顺序与配置一致
+///
+/// 只要有一个图床上传成功即视为成功，全部失败时返回最后一个错误
+async fn upload_to_hosts(
+    hosts: &[Arc<dyn ImageHost>],
+    operation_name: &str,
+    name: &str,
+    data: &[u8],
+    cancelled: Arc<AtomicBool>,
+) -> Result<Vec<(&'static str, String)>> {
+    let mut mirrors = vec![];
+    let mut last_err = None;
+    for host in hosts {
+        match retry_network_operation(
+            &format!("{} ({})", operation_name, host.name()),
+            || host.upload(name, data),
+            Some(cancelled.clone()),
+        )
+        .await
+        {
+            Ok(url) => mirrors.push((host.name(), url)),
+            Err(e) => {
+                warn!("{} 到 {} 失败: {}", operation_name, host.name(), e);
+                last_err = Some(e);
+            }
+        }
+    }
+    match last_err {
+        Some(e) if mirrors.is_empty() => Err(e),
+        _ => Ok(mirrors),
+    }
+}
+
+/// 上传过程中无法下载的页面，根据配置决定是否容忍
+#[derive(Debug)]
+struct MissingPages {
+    config: PartialPublish,
+    pages: std::sync::Mutex<Vec<(EhPageUrl, String)>>,
+}
+
+impl MissingPages {
+    fn new(config: PartialPublish) -> Self {
+        Self { config, pages: Default::default() }
+    }
+
+    /// 判断是否可以跳过该页面继续上传，可以时记录下来
+    ///
+    /// 只有解析和下载失败的页面可以跳过，图床和数据库的错误不在此列
+    fn tolerate(&self, page: &EhPageUrl, err: &UploadError) -> bool {
+        if self.config.policy == MissingPagePolicy::Skip
+            || !matches!(err, UploadError::PageResolve { .. } | UploadError::Download { .. })
+            || err.blocked().is_some()
+        {
+            return false;
+        }
+        let mut pages = self.pages.lock().unwrap();
+        if pages.len() >= self.config.max_missing {
+            return false;
+        }
+        warn!("第 {} 页无法下载，暂时跳过：{}", page.page(), err);
+        pages.push((page.clone(), err.to_string()));
+        true
+    }
+
+    fn take(&self) -> Vec<(EhPageUrl, String)> {
+        std::mem::take(&mut *self.pages.lock().unwrap())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UploadProgress {
+    pub gallery_id: i32,
+    pub total_pages: usize,
+    pub downloaded_pages: usize,
+    pub uploaded_pages: usize, 
+    pub parsed_pages: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExloliUploader {
+    ehentai: EhClient,
+    telegraph: Telegraph,
+    bot: Bot,
+    config: Config,
+    trans: EhTagTransDB,
+    hosts: Vec<Arc<dyn ImageHost>>,
+    /// 每个搜索配置的过滤规则
+    filters: Arc<HashMap<String, ContentFilter>>,
+    /// 是否已经因为封禁通知过管理员
+    blocked: Arc<AtomicBool>,
+    /// 最近一次获取到的图片额度，以及获取时间
+    quota: Arc<std::sync::Mutex<Option<(EhQuota, NaiveDateTime)>>>,
+    http: HttpFactory,
+    /// 用于检查 telegraph 文章和图床状态
+    client: Client,
+    /// 等待上传任务结果的调用者，任务由 run_jobs 依次执行
+    queue: Arc<UploadQueue>,
+}
+
+impl ExloliUploader {
+    pub async fn new(
+        config: Config,
+        ehentai: EhClient,
+        bot: Bot,
+        trans: EhTagTransDB,
+    ) -> Result<Self> {
+        let telegraph = Telegraph::new(&config.telegraph.author_name)
+            .author_url(&config.telegraph.author_url)
+            .access_token(&config.telegraph.access_token)
+            .create()
+            .await?;
+        let hosts = image_host::from_config(&config)?;
+        info!("使用图床: {:?}", hosts.iter().map(|h| h.name()).collect::<Vec<_>>());
+        let mut filters = HashMap::new();
+        for profile in config.profiles() {
+            let filter = ContentFilter::new(profile.filter.as_ref().unwrap_or(&config.filter))?;
+            if filters.insert(profile.name.clone(), filter).is_some() {
+                bail!("搜索配置 {} 重复", profile.name);
+            }
+        }
+        let filters = Arc::new(filters);
+        let blocked = Arc::new(AtomicBool::new(false));
+        let quota = Default::default();
+        let http = HttpFactory::new(&config);
+        let client = http.client(ClientKind::Other)?;
+        Ok(Self {
+            ehentai,
+            config,
+            telegraph,
+            bot,
+            trans,
+            hosts,
+            filters,
+            blocked,
+            quota,
+            http,
+            client,
+            queue: Default::default(),
+        })
+    }
+
+    /// 按照每个搜索配置的间隔定时扫描，同时执行上传队列中的任务
+    pub async fn start(&self) {
+        let profiles = self.config.profiles();
+        let scans = future::join_all(profiles.into_iter().map(|profile| self.run_profile(profile)));
+        future::join3(scans, self.run_updates(), self.run_jobs()).await;
+    }
+
+    /// 定期检查已发布的画廊是否有更新，每个画廊按照自己的检查时间进行，和是否出现在搜索结果中无关
+    async fn run_updates(&self) {
+        // 每轮最多检查的画廊数量，剩下的留到下一轮
+        const BATCH_SIZE: i32 = 100;
+        const INTERVAL: Duration = Duration::from_secs(600);
+        info!("画廊更新任务已启动，检查间隔: {:?}", INTERVAL);
+        loop {
+            match GalleryEntity::list_due(BATCH_SIZE).await {
+                Ok(galleries) if !galleries.is_empty() => {
+                    info!("检查 {} 个画廊的更新", galleries.len());
+                    let urls = galleries.iter().map(|g| g.url()).collect::<Vec<_>>();
+                    if let Err(err) = self.update_galleries(&urls, true).await {
+                        error!("检查画廊更新失败: {:?}", err);
+                    }
+                    self.report_accounts().await;
+                }
+                Ok(_) => {}
+                Err(err) => error!("获取待更新的画廊失败: {:?}", err),
+            }
+            time::sleep(INTERVAL).await;
+        }
+    }
+
+    /// 每隔 interval 扫描一次指定的搜索配置
+    async fn run_profile(&self, profile: Profile) {
+        let interval = profile.interval.unwrap_or(self.config.interval);
+        info!("[{}] 定时扫描任务已启动，扫描间隔: {:?}", profile.name, interval);
+        info!("[{}] 搜索参数: {:?}", profile.name, profile.search_params);
+        info!("[{}] 搜索数量: {}，发布到: {}", profile.name, profile.search_count, profile.channel_id);
+
+        loop {
+            let scan_start_time = std::time::Instant::now();
+            info!("🔄 [{}] 开始扫描 E 站本子 ({})", profile.name, chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC"));
+            
+            // 添加 panic 捕获确保单次扫描失败不会终止循环
+            let scan_result = std::panic::AssertUnwindSafe(async {
+                self.check(&profile).await
+            }).catch_unwind().await;
+            
+            match scan_result {
+                Ok(()) => {
+                    let scan_duration = scan_start_time.elapsed();
+                    info!("✅ 扫描完毕，耗时 {:?}，等待 {:?} 后继续下次扫描", scan_duration, interval);
+                }
+                Err(panic_err) => {
+                    let scan_duration = scan_start_time.elapsed();
+                    error!("❌ 扫描过程中发生严重错误（panic），耗时 {:?}: {:?}", scan_duration, panic_err);
+                    error!("将在 {:?} 后重试下次扫描", interval);
+                }
+            }
+            
+            info!("⏰ 下次扫描将在 {} 开始", 
+                  (chrono::Utc::now() + chrono::Duration::from_std(interval).unwrap())
+                  .format("%Y-%m-%d %H:%M:%S UTC"));
+                  
+            // 确保即使在错误情况下也继续运行
+            time::sleep(interval).await;
+        }
+    }
+
+    /// 依次执行上传队列中的任务，启动时会先继续上次退出时未完成的任务
+    async fn run_jobs(&self) {
+        info!("上传队列已启动");
+        loop {
+            let jobs = match UploadJobEntity::list_unfinished().await {
+                Ok(jobs) => jobs,
+                Err(e) => {
+                    error!("获取未完成的上传任务失败: {}", e);
+                    time::sleep(Duration::from_secs(60)).await;
+                    continue;
+                }
+            };
+            if jobs.is_empty() {
+                self.queue.wait().await;
+                continue;
+            }
+
+            for job in jobs {
+                let (id, gallery_id) = (job.id, job.gallery_id);
+                if job.state != UploadJobState::Queued {
+                    info!("继续上次未完成的上传任务 {}（{:?}）: {}", id, job.state, job.url().url());
+                }
+                // 在单独的任务中执行，panic 时也能把结果告诉调用者
+                let this = self.clone();
+                let result = flatten(tokio::spawn(async move { this.run_job(job).await })).await;
+                if let Err(e) = &result {
+                    if let Err(err) = UploadJobEntity::fail(id, &e.to_string()).await {
+                        error!("更新上传任务状态失败: {}", err);
+                    }
+                    // 图片阶段的失败会留下一部分页面记录，清理掉以免发布不完整的内容
+                    if e.page().is_some() {
+                        let _ = PageEntity::delete_by_gallery(gallery_id).await;
+                    }
+                    warn!("上传任务 {} 失败，画廊 {}: {}", id, gallery_id, e);
+                }
+                self.queue.finish(id, result).await;
+                time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+
+    /// 增量扫描，只处理比上次扫描到的画廊更新的画廊，以及已到重试时间的失败画廊
+    ///
+    /// 遇到已经扫描过的画廊时就停止翻页，更早的画廊需要通过 backfill 手动回溯
+    #[tracing::instrument(skip_all, fields(profile = profile.name))]
+    async fn check(&self, profile: &Profile) {
+        // 添加整体错误捕获，确保扫描循环不会因为任何错误而中断
+        let result = std::panic::AssertUnwindSafe(async {
+            if let Some(quota) = self.refresh_quota().await {
+                info!("图片额度：{}/{}", quota.used, quota.limit);
+            }
+
+            let mark = match ScanStateEntity::get(&profile.name).await {
+                Ok(state) => state.map(|s| s.max_gallery_id),
+                Err(e) => {
+                    error!("获取扫描进度失败: {}", e);
+                    None
+                }
+            };
+            let search = self.ehentai.search_iter(&profile.search_params);
+            let mut galleries = select_new(search, mark, profile.search_count).await;
+            info!("本次处理 {} 个新画廊", galleries.len());
+            let batch = galleries.iter().map(|g| g.id()).collect::<Vec<_>>();
+
+            // 失败的画廊可能已经不在新画廊的范围内，需要单独重试
+            match GalleryFailureEntity::list_due(&profile.name).await {
+                Ok(failures) => {
+                    for failure in failures {
+                        if galleries.iter().all(|g| g.id() != failure.gallery_id) {
+                            galleries.push(failure.url());
+                        }
+                    }
+                }
+                Err(e) => error!("获取待重试的画廊失败: {}", e),
+            }
+
+            let (blocked, held) = self.scan(profile, galleries).await;
+            // 扫描被中断时不更新进度，没处理完的画廊下次扫描时还能看到
+            if let (None, Some(mark)) = (&blocked, next_mark(&batch, &held)) {
+                if let Err(e) = ScanStateEntity::update(&profile.name, mark).await {
+                    error!("更新扫描进度失败: {}", e);
+                }
+            }
+
+            self.report_accounts().await;
+            self.report_blocked(blocked).await;
+            Result::<()>::Ok(())
+        });
+
+        if let Err(panic_err) = std::panic::AssertUnwindSafe(result).catch_unwind().await {
+            error!("扫描过程中发生严重错误（panic）: {:?}", panic_err);
+            // 即使发生panic，也要让程序继续运行
+        }
+
+        info!("check函数执行完毕，程序将继续运行");
+    }
+
+    /// 回溯扫描，无视扫描进度，处理搜索结果的前 count 个画廊，返回处理的画廊数量
+    ///
+    /// 用于首次部署或者修改搜索参数之后补充更早的画廊
+    #[tracing::instrument(skip(self, profile), fields(profile = profile.name))]
+    pub async fn backfill(&self, profile: &Profile, count: usize) -> Result<usize> {
+        let galleries = self
+            .ehentai
+            .search_iter(&profile.search_params)
+            .take(count)
+            .collect::<Vec<_>>()
+            .await;
+        let total = galleries.len();
+        let batch = galleries.iter().map(|g| g.id()).collect::<Vec<_>>();
+
+        let (blocked, held) = self.scan(profile, galleries).await;
+        self.report_accounts().await;
+        if let Some(reason) = blocked {
+            return Err(anyhow!("E 站访问受限，回溯已中断：{}", reason));
+        }
+        if let Some(mark) = next_mark(&batch, &held) {
+            ScanStateEntity::update(&profile.name, mark).await?;
+        }
+        Ok(total)
+    }
+
+    /// 依次更新或者上传画廊，被封禁或者额度用完时中断
+    ///
+    /// 返回中断的原因，以及被推迟到下次扫描的画廊，扫描进度不能越过这些画廊
+    async fn scan(&self, profile: &Profile, galleries: Vec<EhGalleryUrl>) -> (Option<String>, Vec<i32>) {
+        // 被封禁或者额度用完时停止本次扫描，等到下次扫描再尝试
+        // NOTE: 已上传画廊的更新由 run_updates 负责，这里只处理新画廊
+        let mut blocked = None;
+        let mut held = vec![];
+
+        let total = galleries.len();
+        let mut processed_count = 0;
+        let mut error_count = 0;
+        
+        for next in galleries {
+            if blocked.is_some() {
+                break;
+            }
+            processed_count += 1;
+            info!("处理画廊 {}/{}: {}", processed_count, total, next.url());
+            
+            // 捕获单个画廊的异常，避免中断整个扫描循环
+            let gallery_result = std::panic::AssertUnwindSafe(async {
+                // 失败过的画廊要等到退避时间之后才重试，被暂停的画廊需要管理员手动解除
+                match GalleryFailureEntity::get(next.id()).await {
+                    Ok(Some(failure)) if !failure.is_due() => {
+                        debug!(
+                            "画廊 {} 已失败 {} 次，{}",
+                            next.url(),
+                            failure.attempts,
+                            if failure.parked {
+                                "已暂停自动上传".to_string()
+                            } else {
+                                format!("将在 {} 后重试", failure.next_retry_at)
+                            }
+                        );
+                        return;
+                    }
+                    Err(err) => error!("获取画廊失败记录失败: {}", err),
+                    _ => (),
+                }
+                if let Err(err) = self.auto_upload(profile, &next).await {
+                    // 剩余额度不够时留到下次扫描，不计入失败次数
+                    if matches!(err, UploadError::Postponed { .. }) && err.is_retryable() {
+                        info!("画廊 {} 推迟到下次扫描：{}", next.url(), err);
+                        held.push(next.id());
+                        return;
+                    }
+                    error_count += 1;
+                    error!("check_and_upload 失败: {:?}\n{}", err, Backtrace::force_capture());
+                    // 与画廊本身无关的错误不计入失败次数
+                    if let Some(e) = err.blocked() {
+                        // 还有其他可用账号时继续扫描，该画廊在下次扫描时重试
+                        if self.ehentai.report(e) {
+                            warn!("账号受限，已切换账号，画廊 {} 将在下次扫描时重试", next.url());
+                            held.push(next.id());
+                        } else {
+                            blocked = Some(e.to_string());
+                        }
+                        return;
+                    }
+                    if let Err(e) = self.record_failure(profile, &next, &err).await {
+                        error!("记录画廊失败信息失败: {}", e);
+                    }
+                }
+            })
+            .catch_unwind()
+            .await;
+
+            if let Err(panic_err) = gallery_result {
+                error_count += 1;
+                error!(
+                    "处理画廊 {} 时发生未捕获的 panic，已跳过该画廊，等待下次扫描: {:?}",
+                    next.url(),
+                    panic_err
+                );
+            }
+            
+            // 确保即使在错误情况下也继续处理下一个画廊
+            info!("完成画廊 {} 的处理，准备处理下一个画廊", next.url());
+            time::sleep(Duration::from_secs(1)).await;
+        }
+        
+        info!(
+            "扫描完成：处理了 {} 个画廊，遇到 {} 个错误，推迟 {} 个",
+            processed_count,
+            error_count,
+            held.len()
+        );
+        (blocked, held)
+    }
+
+    /// 检查指定画廊是否已经上传，如果没有则进行上传
+    ///
+    /// 为了避免绕晕自己，这次不考虑父子画廊，只要 id 不同就视为新画廊，只要是新画廊就进行上传
+    #[tracing::instrument(skip(self, profile))]
+    pub async fn try_upload(
+        &self,
+        gallery: &EhGalleryUrl,
+        check: bool,
+        profile: &Profile,
+    ) -> Result<(), UploadError> {
+        self.try_upload_with_progress(
+            gallery,
+            check,
+            profile,
+            None::<fn(UploadProgress) -> std::future::Ready<()>>,
+        )
+        .await
+    }
+
+    /// 带进度回调的上传方法
+    #[tracing::instrument(skip(self, profile, progress_callback))]
+    pub async fn try_upload_with_progress<F, Fut>(
+        &self,
+        gallery: &EhGalleryUrl,
+        check: bool,
+        profile: &Profile,
+        progress_callback: Option<F>,
+    ) -> Result<(), UploadError>
+    where
+        F: Fn(UploadProgress) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let progress = progress_callback
+            .map(|f| Box::new(move |p| f(p).boxed()) as ProgressCallback);
+        self.upload(gallery, check, profile, false, None, progress).await
+    }
+
+    /// 定时扫描时使用的上传方法，新画廊需要先通过内容过滤
+    async fn auto_upload(&self, profile: &Profile, gallery: &EhGalleryUrl) -> Result<(), UploadError> {
+        self.upload(gallery, true, profile, true, None, None).await
+    }
+
+    /// 将画廊加入上传队列，并等待任务完成
+    ///
+    /// 消息默认回复父画廊的消息，指定 reply_to 时回复该画廊的消息
+    async fn upload(
+        &self,
+        gallery: &EhGalleryUrl,
+        check: bool,
+        profile: &Profile,
+        filter: bool,
+        reply_to: Option<i32>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<(), UploadError> {
+        let channel = profile.channel_key();
+        if check
+            && GalleryEntity::check(gallery.id()).await?
+            && MessageEntity::get_by_gallery(&channel, gallery.id()).await?.is_some()
+        {
+            return Ok(());
+        }
+
+        // 需要过滤时，先获取画廊信息，不符合规则的画廊不会进入任务队列
+        let mut prefetched = None;
+        if filter {
+            let gallery = self.ehentai.get_gallery(gallery).await?;
+            let reject = self.filters.get(&profile.name).and_then(|f| f.reject_reason(&gallery));
+            if let Some(reason) = reject {
+                info!("画廊 {} 不符合过滤规则，跳过：{}", gallery.url.url(), reason);
+                return Ok(());
+            }
+            // 剩余额度不够上传整个画廊时推迟到下次扫描，避免上传到一半额度耗尽
+            if let Some(quota) = self.refresh_quota().await {
+                let need = gallery.pages.len() as u32 + self.config.quota.reserve;
+                if need > quota.remaining() {
+                    return Err(UploadError::Postponed {
+                        need,
+                        remaining: quota.remaining(),
+                        limit: quota.limit,
+                    });
+                }
+            }
+            prefetched = Some(gallery);
+        }
+
+        // 先写入任务队列，这样即使中途重启，也能在启动时继续这个任务
+        let result = self.queue.push(gallery, check, &channel, reply_to, prefetched, progress).await?;
+        result.await.unwrap_or_else(|_| Err(UploadError::Job("上传任务被取消".into())))
+    }
+
+    /// 执行一个上传任务，上传画廊并发布到任务指定的频道，失败时由 run_jobs 更新任务状态
+    async fn run_job(&self, job: UploadJobEntity) -> Result<(), UploadError> {
+        let url = job.url();
+        let (prefetched, progress_callback) = self.queue.take(job.id).await;
+        // 强制上传的任务在进入上传阶段前已经清理过旧记录，继续上传时已上传的图片可以直接复用
+        let check = job.check_exist
+            || matches!(job.state, UploadJobState::Uploading | UploadJobState::Publishing);
+        // 搜索配置被删除时发布到默认频道
+        let profile = job
+            .channel_id
+            .as_deref()
+            .and_then(|channel| self.config.profile_for_channel(channel))
+            .unwrap_or_else(|| self.config.default_profile());
+        let channel = profile.channel_key();
+
+        // 上次退出时可能已经写入了画廊记录，只是没来得及更新任务状态
+        if check
+            && GalleryEntity::check(url.id()).await?
+            && MessageEntity::get_by_gallery(&channel, url.id()).await?.is_some()
+        {
+            UploadJobEntity::update_state(job.id, UploadJobState::Done).await?;
+            return Ok(());
+        }
+
+        UploadJobEntity::update_state(job.id, UploadJobState::Resolving).await?;
+        let gallery = match prefetched {
+            Some(v) => v,
+            None => self.ehentai.get_gallery(&url).await?,
+        };
+
+        UploadJobEntity::update_state(job.id, UploadJobState::Uploading).await?;
+        self.upload_gallery_image_with_progress(&gallery, check, progress_callback).await?;
+
+        UploadJobEntity::update_state(job.id, UploadJobState::Publishing).await?;
+        let article =
+            self.publish_telegraph_article(&gallery).await.map_err(UploadError::Telegraph)?;
+        let text = self.create_message_text(&gallery, &article.url).await;
+
+        // 评论需要在发布消息之前保存，讨论组收到转发的消息时会读取它们
+        if let Err(e) = CommentEntity::replace(gallery.url.id(), &gallery.comments).await {
+            warn!("保存画廊 {} 的评论失败: {}", gallery.url.url(), e);
+        }
+
+        let reply_to = job.reply_to.or(gallery.parent.as_ref().map(|p| p.id()));
+        let msg = if let Some(parent) = reply_to {
+            if let Some(pmsg) = MessageEntity::get_by_gallery(&channel, parent).await? {
+                self.bot
+                    .send_message(profile.channel_id.clone(), text)
+                    .reply_to_message_id(MessageId(pmsg.id))
+                    .await?
+            } else {
+                self.bot.send_message(profile.channel_id.clone(), text).await?
+            }
+        } else {
+            self.bot.send_message(profile.channel_id.clone(), text).await?
+        };
+
+        MessageEntity::create(&channel, msg.id.0, gallery.url.id()).await?;
+        TelegraphEntity::create(gallery.url.id(), &article.url).await?;
+        GalleryEntity::create(&gallery).await?;
+        let next_check = Utc::now().naive_utc() + check_interval(chrono::Duration::zero());
+        GalleryEntity::update_next_check(gallery.url.id(), next_check).await?;
+        UploadJobEntity::update_state(job.id, UploadJobState::Done).await?;
+        GalleryFailureEntity::delete(gallery.url.id()).await?;
+        Ok(())
+    }
+
+    /// 检查指定画廊是否有更新，比如标题、标签
+    #[tracing::instrument(skip(self))]
+    pub async fn try_update(&self, gallery: &EhGalleryUrl, check: bool) -> Result<()> {
+        self.update_galleries(std::slice::from_ref(gallery), check).await
+    }
+
+    /// 批量检查画廊是否有更新，元数据通过 API 每次最多获取 25 个
+    ///
+    /// 检查完毕的画廊会按照发布时间安排下次检查，check 为 false 时忽略检查时间强制更新
+    #[tracing::instrument(skip(self, galleries))]
+    pub async fn update_galleries(&self, galleries: &[EhGalleryUrl], check: bool) -> Result<()> {
+        let mut tracked = HashMap::new();
+        for gallery in galleries {
+            if let Some(v) = self.need_update(gallery, check).await? {
+                tracked.insert(gallery.id(), v);
+            }
+        }
+        if tracked.is_empty() {
+            return Ok(());
+        }
+
+        // 无论更新是否成功都安排下次检查，避免失败的画廊每一轮都被重复检查
+        // NOTE: 被封禁时获取元数据会直接返回错误，这些画廊会留到下一轮
+        let now = Utc::now().naive_utc();
+        let schedule = tracked
+            .iter()
+            .map(|(id, (_, messages))| (*id, now + check_interval(now.date() - messages[0].publish_date)))
+            .collect::<Vec<_>>();
+
+        let urls = galleries.iter().filter(|g| tracked.contains_key(&g.id())).cloned().collect::<Vec<_>>();
+        for meta in self.ehentai.get_gallery_meta(&urls).await? {
+            let Some((entity, messages)) = tracked.remove(&meta.gid) else { continue };
+            let pages = entity.pages;
+            if let Err(err) = self.apply_update(entity, &messages, &meta).await {
+                error!("更新画廊 {} 失败: {:?}", meta.url(), err);
+            }
+            // 只有元数据显示画廊有变化时才获取画廊页面，减少页面请求
+            let page = if pages != meta.filecount as i32 {
+                // 页数变化时需要完整的页面列表，评论和新版本也使用同一次请求的结果
+                match self.ehentai.get_gallery(&meta.url()).await {
+                    Ok(gallery) => {
+                        if let Err(err) = self.update_pages(&gallery, pages).await {
+                            error!("更新画廊 {} 的页面失败: {:?}", meta.url(), err);
+                        }
+                        Some(gallery.page_info())
+                    }
+                    Err(err) => {
+                        error!("获取画廊 {} 失败: {:?}", meta.url(), err);
+                        None
+                    }
+                }
+            } else if meta.expunged || meta.has_newer_version() {
+                match self.ehentai.get_gallery_page(&meta.url()).await {
+                    Ok(page) => Some(page),
+                    Err(err) => {
+                        error!("获取画廊 {} 的页面失败: {:?}", meta.url(), err);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            if let Some(page) = page {
+                if let Err(err) = self.update_from_page(&messages, &meta, &page).await {
+                    error!("更新画廊 {} 的评论和新版本失败: {:?}", meta.url(), err);
+                }
+            }
+        }
+        for (id, at) in schedule {
+            GalleryEntity::update_next_check(id, at).await?;
+        }
+        Ok(())
+    }
+
+    /// 从画廊页面更新评论，并处理画廊的新版本
+    ///
+    /// 画廊有新版本时，在每个频道中回复旧消息发布最新版本，并把投票转移到新版本，
+    /// 画廊被隐藏并且没有新版本时，直接标记为删除
+    async fn update_from_page(
+        &self,
+        messages: &[MessageEntity],
+        meta: &EhGalleryMeta,
+        page: &EhGalleryPage,
+    ) -> Result<()> {
+        let url = meta.url();
+        CommentEntity::replace(meta.gid, &page.comments).await?;
+        if let Some(rating_count) = page.rating_count {
+            GalleryEntity::update_rating_count(meta.gid, rating_count).await?;
+        }
+
+        let Some(newest) = page.newer_versions.last() else {
+            if meta.expunged {
+                info!("画廊 {} 已被隐藏，标记为删除", url);
+                GalleryEntity::update_deleted(meta.gid, true).await?;
+            }
+            return Ok(());
+        };
+
+        info!("画廊 {} 有新版本 {}", url, newest);
+        for message in messages {
+            let Some(profile) = self.config.profile_for_channel(&message.channel_id) else {
+                warn!("频道 {} 已不在配置中，跳过画廊 {} 的新版本", message.channel_id, url);
+                continue;
+            };
+            self.upload(newest, true, &profile, false, Some(meta.gid), None).await?;
+            // 新版本已经有自己的投票时保留它，否则沿用旧版本的投票
+            if PollEntity::get_by_gallery(&message.channel_id, newest.id()).await?.is_none() {
+                PollEntity::transfer(&message.channel_id, meta.gid, newest.id()).await?;
+            }
+        }
+        GalleryEntity::update_deleted(meta.gid, true).await?;
+        Ok(())
+    }
+
+    /// 判断已上传的画廊是否到了需要检查更新的时间
+    async fn need_update(
+        &self,
+        gallery: &EhGalleryUrl,
+        check: bool,
+    ) -> Result<Option<(GalleryEntity, Vec<MessageEntity>)>> {
+        let entity = match GalleryEntity::get(gallery.id()).await? {
+            Some(v) => v,
+            _ => return Ok(None),
+        };
+        // 同一个画廊可能发布在多个频道中，按最早发布的消息计算更新周期
+        let messages = MessageEntity::list_by_gallery(gallery.id()).await?;
+        if messages.is_empty() {
+            return Ok(None);
+        }
+        if check && entity.next_check_at.is_some_and(|t| t > Utc::now().naive_utc()) {
+            return Ok(None);
+        }
+
+        Ok(Some((entity, messages)))
+    }
+
+    /// 标题或者标签有变化时，更新频道消息和数据库记录
+    async fn apply_update(
+        &self,
+        mut entity: GalleryEntity,
+        messages: &[MessageEntity],
+        meta: &EhGalleryMeta,
+    ) -> Result<()> {
+        let tags = meta.tags();
+        if tags != entity.tags.0 || meta.title != entity.title {
+            entity.title = meta.title.clone();
+            entity.title_jp = meta.title_jp();
+            entity.tags.0 = tags;
+            let telegraph = TelegraphEntity::get(entity.id).await?.unwrap();
+            let text = self.create_message_text(&entity, &telegraph.url).await;
+            for message in messages {
+                self.bot.edit_message_text(message.chat(), MessageId(message.id), &text).await?;
+            }
+        }
+
+        GalleryEntity::update_meta(meta).await?;
+
+        Ok(())
+    }
+
+    /// 找到画廊在指定频道中应该使用的投票 ID
+    ///
+    /// 画廊自己没有投票时，不断向上查找父画廊，直到某个祖先画廊在该频道中有投票，
+    /// 这样同一个作品的所有版本共用一个投票；一直找到根画廊都没有投票时，分配一个新的 ID。
+    /// 父画廊优先从数据库中查找，只有数据库里缺失的祖先画廊才会通过 E 站获取并补上，
+    /// 访问 E 站失败时不影响发送投票，直接分配新的 ID
+    pub async fn resolve_poll(&self, channel_id: &str, gallery: &GalleryEntity) -> Result<i64> {
+        let step = |url| self.poll_step(channel_id, url);
+        if let Some(poll) = find_ancestor_poll(gallery.url(), step).await {
+            return Ok(poll);
+        }
+        Ok(PollEntity::allocate_id(gallery.id).await?)
+    }
+
+    /// 查找画廊在该频道中的投票，没有投票时返回它的父画廊
+    async fn poll_step(&self, channel_id: &str, url: EhGalleryUrl) -> Result<Ancestor> {
+        if let Some(poll) = PollEntity::get_by_gallery(channel_id, url.id()).await? {
+            return Ok(Ancestor::Poll(poll.id));
+        }
+        if let Some(gallery) = GalleryEntity::get_with_deleted(url.id()).await? {
+            let Some(parent) = gallery.parent else { return Ok(Ancestor::Root) };
+            if let Some(parent) = GalleryEntity::get_with_deleted(parent).await? {
+                return Ok(Ancestor::Parent(parent.url()));
+            }
+        }
+
+        // 数据库中没有该画廊或者它的父画廊，需要从 E 站获取
+        let meta = self
+            .ehentai
+            .get_gallery_meta(std::slice::from_ref(&url))
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow!("找不到画廊 {} 的元数据", url))?;
+        if !GalleryEntity::check(meta.gid).await? {
+            debug!("补充祖先画廊 {} 的记录", meta.gid);
+            GalleryEntity::create_from_meta(&meta).await?;
+        }
+        Ok(meta.parent().map_or(Ancestor::Root, Ancestor::Parent))
+    }
+
+    /// 画廊的页数发生变化时，对比页面列表和已记录的页面，只上传有变化的页面，然后重新发布
+    ///
+    /// 页码对应的图片不同的记录会先被删除，之后和新增的页面一起重新处理
+    async fn update_pages(&self, gallery: &EhGallery, before: i32) -> Result<()> {
+        let url = &gallery.url;
+        let current =
+            gallery.pages.iter().map(|p| (p.page(), p.hash())).collect::<HashMap<_, _>>();
+        let stored = PageEntity::list_hashes(url.id()).await?.into_iter().collect::<HashMap<_, _>>();
+
+        for (page, hash) in &stored {
+            if current.get(page) != Some(&hash.as_str()) {
+                PageEntity::delete(url.id(), *page).await?;
+            }
+        }
+        let changed = current
+            .iter()
+            .filter(|(page, hash)| stored.get(page).map(String::as_str) != Some(**hash))
+            .count();
+        info!("画廊 {} 的页数从 {} 变为 {}，{} 页需要更新", url, before, gallery.pages.len(), changed);
+
+        self.upload_gallery_image_with_progress(
+            gallery,
+            true,
+            None::<fn(UploadProgress) -> std::future::Ready<()>>,
+        )
+        .await?;
+        GalleryEntity::create(gallery).await?;
+
+        let entity = GalleryEntity::get(url.id()).await?.ok_or(anyhow!("找不到画廊"))?;
+        let note = format!("{} → {} 页，{} 页有变化", before, gallery.pages.len(), changed);
+        self.republish_with_note(&entity, Some(&note)).await
+    }
+
+    /// 重新发布指定画廊的文章，并更新所有频道中的消息
+    pub async fn republish(&self, gallery: &GalleryEntity) -> Result<()> {
+        self.republish_with_note(gallery, None).await
+    }
+
+    /// 重新发布文章，note 不为空时在消息末尾注明这次更新的内容
+    async fn republish_with_note(&self, gallery: &GalleryEntity, note: Option<&str>) -> Result<()> {
+        info!("重新发布：{}", gallery.id);
+        let article = self.publish_telegraph_article(gallery).await?;
+        let mut text = self.create_message_text(gallery, &article.url).await;
+        if let Some(note) = note {
+            text.push_str(&format!("\n{}: {}", code_inline(&pad_left("更新", 6)), note));
+        }
+        for msg in MessageEntity::list_by_gallery(gallery.id).await? {
+            self.bot.edit_message_text(msg.chat(), MessageId(msg.id), &text).await?;
+        }
+        TelegraphEntity::update(gallery.id, &article.url).await?;
+        Ok(())
+    }
+
+    /// 检查 telegraph 文章是否正常
+    pub async fn check_telegraph(&self, url: &str) -> Result<bool> {
+        Ok(self.client.head(url).send().await?.status() != StatusCode::NOT_FOUND)
+    }
+
+    /// 检查图片是否仍然可以访问
+    pub async fn check_image(&self, url: &str) -> bool {
+        match self.client.head(url).send().await {
+            Ok(resp) => resp.status().is_success(),
+            Err(e) => {
+                warn!("检测图片 {} 失败: {}", url, e);
+                false
+            }
+        }
+    }
+
+    /// 检查画廊中是否有图片的主地址已经失效，并且存在可以切换的镜像
+    ///
+    /// 同一个画廊的图片可能分布在不同的图床上，因此逐张检测，发现一张失效即返回
+    async fn need_switch_mirror(&self, gallery_id: i32) -> Result<bool> {
+        let mut mirrors = HashMap::<u32, Vec<String>>::new();
+        for mirror in ImageMirrorEntity::get_by_gallery_id(gallery_id).await? {
+            mirrors.entry(mirror.image_id).or_default().push(mirror.url);
+        }
+        for img in ImageEntity::get_by_gallery_id(gallery_id).await? {
+            let url = img.url();
+            let has_mirror = mirrors.get(&img.id).is_some_and(|v| v.iter().any(|m| *m != url));
+            if has_mirror && !self.check_image(&url).await {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl ExloliUploader {
+    /// 带进度回调的图片上传方法
+    async fn upload_gallery_image_with_progress<F, Fut>(
+        &self,
+        gallery: &EhGallery,
+        check: bool,
+        progress_callback: Option<F>,
+    ) -> Result<(), UploadError>
+    where
+        F: Fn(UploadProgress) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        // 扫描所有图片
+        // 对于已经上传过的图片，不需要重复上传，只需要插入 PageEntity 记录即可
+        let mut pages = vec![];
+        let mut already_uploaded = 0;
+        let max_galleries = self.config.ad_image.max_galleries;
+        for page in &gallery.pages {
+            // 被标记为广告的图片直接跳过
+            if let Some(flag) = ImageFlagEntity::get(page.hash()).await? {
+                if flag.kind == ImageFlagKind::Ad {
+                    debug!("跳过广告图片：{} ({})", page.page(), flag.reason);
+                    already_uploaded += 1;
+                    continue;
+                }
+            }
+            if check {
+                // 只有在check=true时才进行hash检查来去重
+                match ImageEntity::get_by_hash(page.hash()).await? {
+                    Some(img)
+                        if PageEntity::count_other_galleries(img.id, page.gallery_id()).await?
+                            >= max_galleries =>
+                    {
+                        // 加上当前画廊，已经超过了 max_galleries 个，大概率是广告
+                        info!("图片 {} 出现在过多画廊中，标记为广告", page.hash());
+                        let reason = format!("出现在超过 {} 个画廊中", max_galleries);
+                        ImageFlagEntity::create(page.hash(), ImageFlagKind::Ad, &reason).await?;
+                        already_uploaded += 1;
+                    }
+                    Some(img) => {
+                        // NOTE: 此处存在重复插入的可能，但是由于 PageEntity::create 使用 OR IGNORE，所以不影响
+                        PageEntity::create(page.gallery_id(), page.page(), img.id).await?;
+                        already_uploaded += 1;
+                    }
+                    None => pages.push(page.clone()),
+                }
+            } else {
+                // check=false时，强制重新下载所有图片，跳过hash检查
+                pages.push(page.clone());
+            }
+        }
+        info!("需要下载&上传 {} 张图片，已存在 {} 张", pages.len(), already_uploaded);
+
+        if pages.is_empty() {
+            MissingPageEntity::delete_by_gallery(gallery.url.id()).await?;
+            // 如果所有图片都已经上传过，触发一次完成回调
+            if let Some(callback) = progress_callback {
+                let final_progress = UploadProgress {
+                    gallery_id: gallery.url.id(),
+                    total_pages: gallery.pages.len(),
+                    downloaded_pages: already_uploaded,
+                    uploaded_pages: already_uploaded,
+                    parsed_pages: already_uploaded,
+                };
+                callback(final_progress).await;
+            }
+            return Ok(());
+        }
+
+        let concurrent = self.config.threads_num;
+        // 使用更合理的通道容量：并发数的2倍，避免内存占用过高
+        let (parse_tx, parse_rx) = tokio::sync::mpsc::channel(concurrent * 2);
+        let client = self.ehentai.clone();
+        
+        // 进度跟踪变量
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use tokio::sync::Mutex;
+        let progress = Arc::new(Mutex::new(UploadProgress {
+            gallery_id: gallery.url.id(),
+            total_pages: gallery.pages.len(),
+            downloaded_pages: already_uploaded,
+            uploaded_pages: already_uploaded,
+            parsed_pages: already_uploaded,
+        }));
+        
+        let callback_arc = progress_callback.map(Arc::new);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let missing = Arc::new(MissingPages::new(self.config.partial_publish.clone()));
+        
+        // 获取图片链接时不要并行，避免触发反爬限制
+        let progress_clone_parser = progress.clone();
+        let callback_clone_parser = callback_arc.clone();
+        let cancel_clone_parser = cancelled.clone();
+        let missing_clone_parser = missing.clone();
+        let mpv = match client.get_mpv(&gallery.url).await {
+            Ok(v) => Some(v),
+            Err(e) => {
+                debug!("无法使用 MPV，将逐页解析图片地址: {}", e);
+                None
+            }
+        };
+        let getter = tokio::spawn(
+            async move {
+                for page in pages {
+                    if cancel_clone_parser.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    // 优先使用 MPV 的 API，失败时再解析单独的页面
+                    let rst = match &mpv {
+                        Some(mpv) => match client.get_image_url_mpv(mpv, &page).await {
+                            Ok(v) => Ok(v),
+                            Err(e) => {
+                                warn!("通过 MPV 获取图片 {} 失败，改为解析页面: {}", page.page(), e);
+                                client.get_image_url(&page).await
+                            }
+                        },
+                        None => client.get_image_url(&page).await,
+                    };
+                    let rst = match rst {
+                        Ok(v) => v,
+                        Err(source) => {
+                            let e = UploadError::PageResolve { page: page.page(), retryable: true, source };
+                            if missing_clone_parser.tolerate(&page, &e) {
+                                continue;
+                            }
+                            cancel_clone_parser.store(true, Ordering::Relaxed);
+                            return Err(e);
+                        }
+                    };
+                    info!("已解析：{}", page.page());
+                    
+                    // 更新解析进度
+                    if let Some(ref callback) = callback_clone_parser {
+                        let mut prog = progress_clone_parser.lock().await;
+                        prog.parsed_pages += 1;
+                        callback(prog.clone()).await;
+                    }
+                    
+                    // 如果发送失败（通道关闭），提前退出
+                    // 使用循环尝试发送，以便能够响应取消信号
+                    let mut item_to_send = (page, rst);
+                    loop {
+                        if cancel_clone_parser.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        match parse_tx.try_send(item_to_send) {
+                            Ok(_) => break,
+                            Err(tokio::sync::mpsc::error::TrySendError::Full(returned_item)) => {
+                                item_to_send = returned_item;
+                                time::sleep(Duration::from_millis(100)).await;
+                            }
+                            Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => {
+                                // Channel closed, exit
+                                return Ok::<(), UploadError>(());
+                            }
+                        }
+                    }
+                    if cancel_clone_parser.load(Ordering::Relaxed) {
+                        break;
+                    }
+                }
+                drop(parse_tx); // 关闭发送端，让接收端知道没有更多数据
+                Ok::<(), UploadError>(())
+            }
+            .in_current_span(),
+        );
+
+        // 创建下载上传的并发任务
+        
+        // 使用 Semaphore 来控制并发数量
+        let semaphore = Arc::new(tokio::sync::Semaphore::new(concurrent));
+        let mut upload_handles = vec![];
+        let parse_rx = Arc::new(tokio::sync::Mutex::new(parse_rx));
+        
+        // 启动并发的下载上传任务
+        for _ in 0..concurrent {
+            let sem = semaphore.clone();
+            let rx = parse_rx.clone();
+            let progress_clone = progress.clone();
+            let callback_clone = callback_arc.clone();
+            let hosts = self.hosts.clone();
+            let transcode = self.config.transcode.clone();
+            let ad_image = self.config.ad_image.clone();
+            let cancelled_clone = cancelled.clone();
+            let missing = missing.clone();
+            
+            let client = self.http.client(ClientKind::Image).map_err(UploadError::Config)?;
+            
+            let handle = tokio::spawn(
+                async move {
+                    loop {
+                        if cancelled_clone.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        // 获取下一个任务
+                        let task = {
+                            let mut rx_guard = rx.lock().await;
+                            rx_guard.recv().await
+                        };
+                        if cancelled_clone.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        
+                        let (page, image) = match task {
+                            Some(data) => data,
+                            None => break, // 没有更多任务
+                        };
+                        
+                        // 获取信号量许可，控制并发
+                        let _permit = sem.acquire().await.unwrap();
+
+                        // 原图下载失败时，使用页面上显示的缩放图作为备选
+                        let EhImage { fileindex, url: original_url, original, preview: preview_url } = image;
+                        let suffix = original_url.rsplit('.').next().unwrap_or("jpg");
+
+                        let filename = format!("{}.{}", page.hash(), suffix);
+
+                        // 下载图片（带网络重试机制和内容验证）
+                        let downloaded = async {
+                            let bytes = match retry_network_operation_with_limit(
+                                &format!("下载图片 {}", page.page()), 7,
+                                || async {
+                                    let response = client.get(&original_url).send().await?;
+                                    ehentai::check_image_url(response.url().as_str())?;
+                                
+                                    // 检查Content-Type
+                                    if let Some(content_type) = response.headers().get("content-type") {
+                                        if let Ok(ct_str) = content_type.to_str() {
+                                            if !ct_str.starts_with("image/") && !ct_str.contains("octet-stream") {
+                                                let msg = format!("响应不是图片类型，Content-Type: {}", ct_str);
+                                                return Err(anyhow!(msg));
+                                            }
+                                        }
+                                    }
+                                
+                                    let bytes = response.bytes().await?;
+                                
+                                    // 检查内容是否为HTML页面
+                                    if bytes.len() > 10 {
+                                        let content_start = String::from_utf8_lossy(&bytes[..std::cmp::min(200, bytes.len())]);
+                                        let content_lower = content_start.trim_start().to_lowercase();
+                                        if content_lower.starts_with("<!doctype html") || 
+                                           content_lower.starts_with("<html") ||
+                                           content_lower.contains("<title>") ||
+                                           content_lower.contains("<form") {
+                                            return Err(anyhow!("下载到的是HTML页面而不是图片"));
+                                        }
+                                    }
+
+                                    // 原图与页面 hash 不一致时，说明下载到的内容损坏或者被替换了，需要重试
+                                    let data = bytes.clone();
+                                    let hash = original.then(|| page.hash().to_string());
+                                    tokio::task::spawn_blocking(move || utils::verify_image(&data, hash.as_deref())).await??;
+                                
+                                    Ok(bytes)
+                                },
+                                Some(cancelled_clone.clone())
+                            ).await {
+                                Ok(b) => Some(b),
+                                Err(e) => {
+                                    // 如果有预览图，尝试降级
+                                    if preview_url.is_some() {
+                                        warn!("下载图片失败 {} (重试后仍失败)，准备尝试预览图: {}", page.page(), e);
+                                        None
+                                    } else {
+                                        error!("下载图片失败 {} (重试后仍失败): {}", page.page(), e);
+                                        return Err(UploadError::download(page.page(), e));
+                                    }
+                                }
+                            };
+                            // 如果原图下载失败，尝试使用预览图
+                            let (final_bytes, final_filename, used_preview) = if let (None, Some(preview)) = (&bytes, &preview_url) {
+                                info!("原图下载失败，使用预览图作为备用方案: {}", preview);
+                                match retry_network_operation_with_limit(
+                                    &format!("下载预览图 {}", page.page()), 7,
+                                    || async {
+                                        let response = client.get(preview).send().await?;
+                                        debug!("预览图响应状态: {}, URL: {}", response.status(), preview);
+                                    
+                                        // 检查Content-Type
+                                        if let Some(content_type) = response.headers().get("content-type") {
+                                            if let Ok(ct_str) = content_type.to_str() {
+                                                debug!("预览图Content-Type: {}", ct_str);
+                                                if !ct_str.starts_with("image/") && !ct_str.contains("octet-stream") {
+                                                    return Err(UploadError::Download {
+                                                        page: page.page(),
+                                                        retryable: false,
+                                                        source: anyhow!("预览图响应不是图片类型，Content-Type: {}", ct_str),
+                                                    }.into());
+                                                }
+                                            }
+                                        }
+                                    
+                                        let preview_bytes = response.bytes().await?;
+                                    
+                                        // 检查内容是否为HTML页面
+                                        if preview_bytes.len() > 10 {
+                                            let content_start = String::from_utf8_lossy(&preview_bytes[..std::cmp::min(200, preview_bytes.len())]);
+                                            let content_lower = content_start.trim_start().to_lowercase();
+                                            if content_lower.starts_with("<!doctype html") || 
+                                           content_lower.starts_with("<html") ||
+                                           content_lower.contains("<title>") ||
+                                           content_lower.contains("<form") {
+                                                return Err(UploadError::Download {
+                                                    page: page.page(),
+                                                    retryable: false,
+                                                    source: anyhow!("下载到的是HTML页面而不是图片"),
+                                                }.into());
+                                            }
+                                        }
+
+                                        let data = preview_bytes.clone();
+                                        tokio::task::spawn_blocking(move || utils::verify_image(&data, None)).await??;
+                                    
+                                        Ok(preview_bytes)
+                                    },
+                                    Some(cancelled_clone.clone())
+                                ).await {
+                                    Ok(preview_bytes) => {
+                                        let preview_suffix = preview.rsplit('.').next().unwrap_or("jpg");
+                                        (Some(preview_bytes), format!("{}_preview.{}", page.hash(), preview_suffix), true)
+                                    },
+                                    Err(e) => {
+                                        error!("图片 {} 原图和预览图都下载失败: {}", page.page(), e);
+                                        return Err(UploadError::download(page.page(), e));
+                                    }
+                                }
+                            } else if let Some(b) = bytes {
+                                (Some(b), filename, false)
+                            } else if let Some(preview) = &preview_url {
+                                // 如果原图失败但有预览图，尝试预览图
+                                info!("原图下载失败，尝试预览图: {}", preview);
+                                match retry_network_operation_with_limit(
+                                    &format!("下载预览图 {}", page.page()), 7,
+                                    || async {
+                                        let response = client.get(preview).send().await?;
+                                        debug!("预览图响应状态: {}, URL: {}", response.status(), preview);
+                                    
+                                        // 检查Content-Type
+                                        if let Some(content_type) = response.headers().get("content-type") {
+                                            if let Ok(ct_str) = content_type.to_str() {
+                                                debug!("预览图Content-Type: {}", ct_str);
+                                                if !ct_str.starts_with("image/") && !ct_str.contains("octet-stream") {
+                                                    return Err(UploadError::Download {
+                                                        page: page.page(),
+                                                        retryable: false,
+                                                        source: anyhow!("预览图响应不是图片类型，Content-Type: {}", ct_str),
+                                                    }.into());
+                                                }
+                                            }
+                                        }
+                                    
+                                        let preview_bytes = response.bytes().await?;
+                                    
+                                        // 检查内容是否为HTML页面
+                                        if preview_bytes.len() > 10 {
+                                            let content_start = String::from_utf8_lossy(&preview_bytes[..std::cmp::min(200, preview_bytes.len())]);
+                                            let content_lower = content_start.trim_start().to_lowercase();
+                                            if content_lower.starts_with("<!doctype html") || 
+                                           content_lower.starts_with("<html") ||
+                                           content_lower.contains("<title>") ||
+                                           content_lower.contains("<form") {
+                                                return Err(UploadError::Download {
+                                                    page: page.page(),
+                                                    retryable: false,
+                                                    source: anyhow!("下载到的是HTML页面而不是图片"),
+                                                }.into());
+                                            }
+                                        }
+
+                                        let data = preview_bytes.clone();
+                                        tokio::task::spawn_blocking(move || utils::verify_image(&data, None)).await??;
+                                    
+                                        Ok(preview_bytes)
+                                    },
+                                    Some(cancelled_clone.clone())
+                                ).await {
+                                    Ok(preview_bytes) => {
+                                        let preview_suffix = preview.rsplit('.').next().unwrap_or("jpg");
+                                        (Some(preview_bytes), format!("{}_preview.{}", page.hash(), preview_suffix), true)
+                                    },
+                                    Err(e) => {
+                                        error!("下载预览图失败 {}: {}", page.page(), e);
+                                        return Err(UploadError::download(page.page(), e));
+                                    }
+                                }
+                            } else {
+                                error!("图片 {} 没有任何可用的下载源", page.page());
+                                return Err(UploadError::download(page.page(), anyhow!("没有任何可用的下载源")));
+                            };
+                            Ok::<_, UploadError>((final_bytes, final_filename, used_preview))
+                        }
+                        .await;
+                        let (final_bytes, final_filename, used_preview) = match downloaded {
+                            Ok(v) => v,
+                            Err(e) => {
+                                if missing.tolerate(&page, &e) {
+                                    continue;
+                                }
+                                // 缺失的页面过多时，跳过整个画廊
+                                cancelled_clone.store(true, Ordering::Relaxed);
+                                return Err(e);
+                            }
+                        };
+
+                        let bytes = final_bytes.unwrap();
+                        debug!("已下载: {} (hash: {}, {}, {} bytes) {}", page.page(),
+                            page.hash(),
+                            if used_preview { "预览图" } else { suffix },
+                            bytes.len(),
+                            if used_preview { "（备选方案）" } else { "" });
+
+                        // 含有二维码的图片标记为广告，不再上传
+                        if ad_image.detect_qrcode {
+                            let data = bytes.clone();
+                            let found = tokio::task::spawn_blocking(move || utils::has_qrcode(&data)).await;
+                            if let Ok(Ok(true)) = found {
+                                info!("图片 {} 含有二维码，标记为广告", page.page());
+                                if let Err(e) = ImageFlagEntity::create(page.hash(), ImageFlagKind::Ad, "包含二维码").await {
+                                    warn!("保存图片标记失败 {}: {}", page.page(), e);
+                                }
+                                if let Some(ref callback) = callback_clone {
+                                    let mut prog = progress_clone.lock().await;
+                                    prog.downloaded_pages += 1;
+                                    prog.uploaded_pages += 1;
+                                    callback(prog.clone()).await;
+                                }
+                                continue;
+                            }
+                        }
+
+                        // 过大的图片在本地缩放并重新编码，GIF 重新编码会丢失动画，因此跳过
+                        let (bytes, final_filename) = if bytes.len() > transcode.max_size
+                            && !final_filename.ends_with(".gif")
+                        {
+                            let data = bytes.clone();
+                            let config = transcode.clone();
+                            match tokio::task::spawn_blocking(move || utils::transcode::transcode(&data, &config)).await {
+                                Ok(Ok(t)) if t.data.len() < bytes.len() => {
+                                    debug!("已重新编码: {} ({} -> {} bytes)", page.page(), bytes.len(), t.data.len());
+                                    (t.data.into(), format!("{}.{}", page.hash(), t.ext))
+                                }
+                                Ok(Ok(_)) => {
+                                    debug!("重新编码未能减小图片 {} 的体积，使用原图", page.page());
+                                    (bytes, final_filename)
+                                }
+                                Ok(Err(e)) => {
+                                    warn!("重新编码图片 {} 失败，使用原图: {}", page.page(), e);
+                                    (bytes, final_filename)
+                                }
+                                Err(e) => {
+                                    warn!("重新编码图片 {} 失败，使用原图: {}", page.page(), e);
+                                    (bytes, final_filename)
+                                }
+                            }
+                        } else {
+                            (bytes, final_filename)
+                        };
+
+                        // 更新下载进度
+                        if let Some(ref callback) = callback_clone {
+                            let mut prog = progress_clone.lock().await;
+                            prog.downloaded_pages += 1;
+                            callback(prog.clone()).await;
+                        }
+
+                        // 上传到所有图床（带网络重试机制）
+                        let mirrors = match upload_to_hosts(
+                            &hosts,
+                            &format!("上传图片 {}", page.page()),
+                            &final_filename,
+                            &bytes,
+                            cancelled_clone.clone(),
+                        ).await {
+                            Ok(mirrors) => mirrors,
+                            Err(e) => {
+                                error!("上传图片失败 {} (重试后仍失败): {}", page.page(), e);
+                                // 当任何一张图片上传失败时，应该跳过整个画廊
+                                cancelled_clone.store(true, Ordering::Relaxed);
+                                return Err(UploadError::HostRejected {
+                                    page: page.page(),
+                                    retryable: true,
+                                    source: e,
+                                });
+                            }
+                        };
+                        debug!("已上传: {} (hash: {}) {}", page.page(), page.hash(), if used_preview { "（预览图）" } else { "" });
+
+                        // 更新上传进度
+                        if let Some(ref callback) = callback_clone {
+                            let mut prog = progress_clone.lock().await;
+                            prog.uploaded_pages += 1;
+                            callback(prog.clone()).await;
+                        }
+
+                        // 保存到数据库，第一个上传成功的图床作为主地址
+                        let upload_url = &mirrors[0].1;
+                        if let Err(e) = ImageEntity::create(fileindex, page.hash(), upload_url).await {
+                            error!("保存图片记录失败 {}: {}", page.page(), e);
+                            // 当任何一张图片保存失败时，应该跳过整个画廊
+                            cancelled_clone.store(true, Ordering::Relaxed);
+                            return Err(UploadError::Database { page: Some(page.page()), source: e });
+                        }
+                        for (host, url) in &mirrors {
+                            if let Err(e) = ImageMirrorEntity::create(fileindex, host, url).await {
+                                // 镜像记录缺失只会影响后续的故障切换，不影响本次发布
+                                warn!("保存镜像记录失败 {} ({}): {}", page.page(), host, e);
+                            }
+                        }
+                        if let Err(e) = PageEntity::create(page.gallery_id(), page.page(), fileindex).await {
+                            error!("保存页面记录失败 {}: {}", page.page(), e);
+                            // 当任何一张图片保存失败时，应该跳过整个画廊
+                            cancelled_clone.store(true, Ordering::Relaxed);
+                            return Err(UploadError::Database { page: Some(page.page()), source: e });
+                        }
+                    }
+                    Ok::<(), UploadError>(())
+                }
+                .in_current_span(),
+            );
+            upload_handles.push(handle);
+        }
+
+        // 等待解析任务完成
+        let parse_result = flatten(getter).await;
+        
+        // 等待所有上传任务完成
+        let mut upload_results = vec![];
+        for handle in upload_handles {
+            upload_results.push(flatten(handle).await);
+        }
+        
+        // 检查是否有任务失败
+        parse_result?;
+        for result in upload_results {
+            result?;
+        }
+
+        // 记录缺失的页面，以便 recheck 时补上，之前缺失但这次下载成功的页面不再记录
+        MissingPageEntity::delete_by_gallery(gallery.url.id()).await?;
+        let missing = missing.take();
+        if !missing.is_empty() {
+            warn!("画廊 {} 有 {} 页无法下载，将在缺页的情况下发布", gallery.url.id(), missing.len());
+        }
+        for (page, error) in missing {
+            MissingPageEntity::create(&page, &error).await?;
+        }
+
+        Ok(())
+    }
+
+    /// 从数据库中读取某个画廊的所有图片，生成一篇 telegraph 文章
+    /// 为了防止画廊被删除后无法更新，此处不应该依赖 EhGallery
+    /// 如果图片数量过多，会创建多个分页文章并返回第一页
+    async fn publish_telegraph_article<T: GalleryInfo>(
+        &self,
+        gallery: &T,
+    ) -> Result<telegraph_rs::Page> {
+        let mut images = self.resolve_image_urls(gallery.url().id()).await?;
+        self.insert_placeholders(gallery.url().id(), &mut images).await?;
+        
+        // Telegraph 单页最大图片数量限制 (约50KB内容限制，每个img标签约100-200字节)
+        const MAX_IMAGES_PER_PAGE: usize = 200;
+        
+        if images.len() <= MAX_IMAGES_PER_PAGE {
+            // 图片数量不多，创建单页文章
+            return self.create_single_telegraph_page(gallery, &images).await;
+        }
+        
+        // 图片数量过多，需要分页处理
+        info!("画廊 {} 有 {} 张图片，需要分页处理", gallery.url().id(), images.len());
+        
+        let total_pages = images.len().div_ceil(MAX_IMAGES_PER_PAGE);
+        let mut created_pages: Vec<telegraph_rs::Page> = Vec::new();
+        
+        for (page_idx, image_chunk) in images.chunks(MAX_IMAGES_PER_PAGE).enumerate() {
+            let page_num = page_idx + 1;
+            let page_title = if total_pages > 1 {
+                format!("{} (第{}页/共{}页)", gallery.title_jp(), page_num, total_pages)
+            } else {
+                gallery.title_jp()
+            };
+            
+            let mut html = String::new();
+            
+            // 只在第一页显示封面
+            if page_idx == 0 && gallery.cover() != 0 && gallery.cover() < images.len() {
+                html.push_str(&format!(r#"<img src="{}">"#, images[gallery.cover()]));
+            }
+            
+            // 添加分页导航（除第一页外）
+            if page_idx > 0 && !created_pages.is_empty() {
+                html.push_str(&format!(r#"<p><a href="{}">← 返回第1页</a></p>"#, created_pages[0].url));
+            }
+            
+            // 添加当前页图片
+            for img in image_chunk {
+                html.push_str(&format!(r#"<img src="{}">"#, img));
+            }
+            
+            // 添加页面信息和导航
+            if total_pages > 1 {
+                html.push_str(&format!("<p>第{}页/共{}页 (图片总数：{})</p>", page_num, total_pages, images.len()));
+                
+                // 添加下一页链接占位符（将在创建下一页后更新）
+                if page_idx < total_pages - 1 {
+                    html.push_str("<p>下一页链接将在创建后添加</p>");
+                }
+            } else {
+                html.push_str(&format!("<p>图片总数：{}</p>", gallery.pages()));
+            }
+            
+            let node = html_to_node(&html);
+            let page = self.telegraph.create_page(&page_title, &node, false).await?;
+            
+            debug!("创建Telegraph分页 {}/{}：{}", page_num, total_pages, page.url);
+            created_pages.push(page);
+        }
+        
+        // 更新页面间的导航链接
+        if created_pages.len() > 1 {
+            for (idx, page) in created_pages.iter().enumerate() {
+                let page_num = idx + 1;
+                let mut html = String::new();
+                
+                // 重新构建HTML内容，添加正确的导航链接
+                if idx == 0 && gallery.cover() != 0 && gallery.cover() < images.len() {
+                    html.push_str(&format!(r#"<img src="{}">"#, images[gallery.cover()]));
+                }
+                
+                // 导航链接
+                let mut nav_links = Vec::new();
+                if idx > 0 {
+                    nav_links.push(format!(r#"<a href="{}">← 上一页</a>"#, created_pages[idx - 1].url));
+                }
+                if idx == 0 && created_pages.len() > 1 {
+                    nav_links.push(format!(r#"<a href="{}">下一页 →</a>"#, created_pages[1].url));
+                }
+                if idx < created_pages.len() - 1 && idx > 0 {
+                    nav_links.push(format!(r#"<a href="{}">下一页 →</a>"#, created_pages[idx + 1].url));
+                }
+                
+                if !nav_links.is_empty() {
+                    html.push_str(&format!("<p>{}</p>", nav_links.join(" | ")));
+                }
+                
+                // 添加当前页图片
+                let start_idx = idx * MAX_IMAGES_PER_PAGE;
+                let end_idx = std::cmp::min(start_idx + MAX_IMAGES_PER_PAGE, images.len());
+                for img in &images[start_idx..end_idx] {
+                    html.push_str(&format!(r#"<img src="{}">"#, img));
+                }
+                
+                // 页面信息
+                html.push_str(&format!("<p>第{}页/共{}页 (图片总数：{})</p>", page_num, created_pages.len(), images.len()));
+                
+                // 底部导航
+                if !nav_links.is_empty() {
+                    html.push_str(&format!("<p>{}</p>", nav_links.join(" | ")));
+                }
+                
+                let node = html_to_node(&html);
+                let page_title = if created_pages.len() > 1 {
+                    format!("{} (第{}页/共{}页)", gallery.title_jp(), page_num, created_pages.len())
+                } else {
+                    gallery.title_jp()
+                };
+                
+                // 更新页面内容
+                if let Err(e) = self.telegraph.edit_page(&page.path, &page_title, &node, false).await {
+                    error!("更新Telegraph分页 {} 导航失败: {}", page_num, e);
+                }
+            }
+        }
+        
+        // 返回第一页
+        Ok(created_pages.into_iter().next().unwrap())
+    }
+    
+    /// 创建单页Telegraph文章
+    async fn create_single_telegraph_page<T: GalleryInfo>(
+        &self,
+        gallery: &T,
+        images: &[String],
+    ) -> Result<telegraph_rs::Page> {
+        let mut html = String::new();
+        if gallery.cover() != 0 && gallery.cover() < images.len() {
+            html.push_str(&format!(r#"<img src="{}">"#, images[gallery.cover()]))
+        }
+        for img in images {
+            html.push_str(&format!(r#"<img src="{}">"#, img));
+        }
+        html.push_str(&format!("<p>图片总数：{}</p>", gallery.pages()));
+
+        let node = html_to_node(&html);
+        let title = gallery.title_jp();
+        Ok(self.telegraph.create_page(&title, &node, false).await?)
+    }
+
+    /// 按照配置，在缺失页面的位置插入占位图片
+    async fn insert_placeholders(&self, gallery_id: i32, images: &mut Vec<String>) -> Result<()> {
+        let config = &self.config.partial_publish;
+        let placeholder = match (config.policy, &config.placeholder_url) {
+            (MissingPagePolicy::Placeholder, Some(url)) => url,
+            _ => return Ok(()),
+        };
+        let missing = MissingPageEntity::get_by_gallery(gallery_id).await?;
+        if missing.is_empty() {
+            return Ok(());
+        }
+        // 被识别为广告或者被过滤的页面不会记录，因此需要按照实际的页码而不是下标来确定插入位置
+        let pages = PageEntity::list_pages(gallery_id).await?;
+        let missing = missing.iter().map(|m| m.page).collect::<Vec<_>>();
+        *images = merge_placeholders(&pages, std::mem::take(images), &missing, placeholder);
+        Ok(())
+    }
+
+    /// 读取画廊的所有图片地址，并按页码排列
+    ///
+    /// 逐张检测有镜像的图片，主地址失效时按照配置中的图床顺序切换到仍然可用的镜像，
+    /// 并将其保存为新的主地址
+    async fn resolve_image_urls(&self, gallery_id: i32) -> Result<Vec<String>> {
+        let images = ImageEntity::get_by_gallery_id(gallery_id).await?;
+        let mut mirrors = HashMap::<u32, Vec<ImageMirrorEntity>>::new();
+        for mirror in ImageMirrorEntity::get_by_gallery_id(gallery_id).await? {
+            mirrors.entry(mirror.image_id).or_default().push(mirror);
+        }
+
+        // 未在配置中出现的图床排在最后
+        let priority = |host: &str| {
+            self.hosts.iter().position(|h| h.name() == host).unwrap_or(self.hosts.len())
+        };
+        let mut urls = Vec::with_capacity(images.len());
+        for img in images {
+            let url = img.url();
+            let candidates = match mirrors.get_mut(&img.id) {
+                Some(v) if v.iter().any(|m| m.url != url) => v,
+                // 没有镜像的图片无法切换
+                _ => {
+                    urls.push(url);
+                    continue;
+                }
+            };
+            if self.check_image(&url).await {
+                urls.push(url);
+                continue;
+            }
+            candidates.sort_by_key(|m| priority(&m.host));
+
+            let mut chosen = None;
+            for mirror in candidates.iter().filter(|m| m.url != url) {
+                if self.check_image(&mirror.url).await {
+                    chosen = Some(mirror.url.clone());
+                    break;
+                }
+                warn!("图片 {} 的镜像 {} 无法访问", img.id, mirror.host);
+            }
+
+            match chosen {
+                Some(new) => {
+                    debug!("图片 {} 切换到镜像：{}", img.id, new);
+                    ImageEntity::update_url(img.id, &new).await?;
+                    urls.push(new);
+                }
+                None => {
+                    warn!("图片 {} 的主地址和所有镜像都无法访问", img.id);
+                    urls.push(url);
+                }
+            }
+        }
+        Ok(urls)
+    }
+
+    /// 为画廊生成一条可供发送的 telegram 消息正文
+    async fn create_message_text<T: GalleryInfo>(
+        &self,
+        gallery: &T,
+        article: &str,
+    ) -> String {
+        // 首先，将 tag 翻译
+        // 并整理成 namespace: #tag1 #tag2 #tag3 的格式
+        let re = Regex::new("[-/· ]").unwrap();
+        let tags = self.trans.trans_tags(gallery.tags());
+        let mut text = String::new();
+        if let Some(category) = gallery.category() {
+            let category = format!("#{}", re.replace_all(category, "_"));
+            text.push_str(&format!("{}: {}\n", code_inline(&pad_left("分类", 6)), category));
+        }
+        for (ns, tag) in tags {
+            let tag = tag
+                .iter()
+                .map(|s| format!("#{}", re.replace_all(s, "_")))
+                .collect::<Vec<_>>()
+                .join(" ");
+            text.push_str(&format!("{}: {}\n", code_inline(&pad_left(&ns, 6)), tag))
+        }
+
+        text.push_str(
+            &format!("{}: {}\n", code_inline("  预览"), link(article, &gallery.title()),),
+        );
+        text.push_str(&format!("{}: {}", code_inline("原始地址"), gallery.url().url()));
+
+        text
+    }
+
+    /// 记录一次自动上传失败，并按照指数退避安排下次重试
+    ///
+    /// 无法自动恢复的错误，或者连续失败次数过多时，会暂停该画廊的自动上传并通知管理员，
+    /// 其余情况只记录日志，避免每次扫描都打扰管理员
+    async fn record_failure(
+        &self,
+        profile: &Profile,
+        gallery: &EhGalleryUrl,
+        err: &UploadError,
+    ) -> Result<()> {
+        let retry = &self.config.retry;
+        let attempts = GalleryFailureEntity::get(gallery.id()).await?.map_or(0, |f| f.attempts) + 1;
+        let parked = !err.is_retryable() || attempts as u32 >= retry.max_failures;
+        let next_retry_at =
+            Utc::now().naive_utc() + chrono::Duration::from_std(retry.delay(attempts as u32))?;
+        GalleryFailureEntity::create(gallery, &profile.name, attempts, &err.to_string(), next_retry_at, parked)
+            .await?;
+
+        if parked {
+            warn!("画廊 {} 已连续失败 {} 次，暂停自动上传", gallery.url(), attempts);
+            self.notify_upload_failure(gallery, err, attempts).await;
+        } else {
+            warn!("画廊 {} 第 {} 次上传失败，将在 {} 后重试", gallery.url(), attempts, next_retry_at);
+        }
+        Ok(())
+    }
+
+    /// 根据错误类型生成提示，通知管理员该画廊已被暂停自动上传
+    async fn notify_upload_failure(&self, gallery: &EhGalleryUrl, err: &UploadError, attempts: i32) {
+        let hint = match err {
+            UploadError::Gallery(EhError::GalleryRemoved(_)) => "画廊已被删除",
+            UploadError::Download { .. } => "原图和预览图都无法获取，可能需要手动检查该画廊",
+            UploadError::HostRejected { .. } => "图床拒绝了上传，请检查图床配置",
+            UploadError::Database { .. } => "数据库写入失败，请检查数据库状态",
+            UploadError::Telegram(_) => "Telegram 请求失败，请检查 bot 权限",
+            UploadError::Config(_) => "无法创建 HTTP 客户端，请检查代理配置",
+            UploadError::Postponed { .. } => "画廊页数超过了图片额度上限，只能手动上传",
+            _ => "请查看日志了解详情",
+        };
+        let page = err.page().map(|p| format!("\n页码: {}", p)).unwrap_or_default();
+        self.notify_admins(&format!(
+            "自动上传失败，已暂停该画廊的自动上传\n\nURL: {}{}\n失败次数: {}\n错误: {}\n\n{}\n处理后可使用 /unpark {} 恢复",
+            gallery.url(),
+            page,
+            attempts,
+            err,
+            hint,
+            gallery.url()
+        ))
+        .await;
+    }
+
+    /// 重新获取图片额度，未启用额度检查或者获取失败时返回 None
+    pub async fn refresh_quota(&self) -> Option<EhQuota> {
+        if !self.config.quota.enabled {
+            return None;
+        }
+        match self.ehentai.get_quota().await {
+            Ok(quota) => {
+                *self.quota.lock().unwrap() = Some((quota, Utc::now().naive_utc()));
+                Some(quota)
+            }
+            Err(e) => {
+                warn!("获取图片额度失败: {}", e);
+                None
+            }
+        }
+    }
+
+    /// 最近一次获取到的图片额度
+    pub fn quota(&self) -> Option<(EhQuota, NaiveDateTime)> {
+        *self.quota.lock().unwrap()
+    }
+
+    /// 当前是否处于被封禁状态
+    pub fn is_blocked(&self) -> bool {
+        self.blocked.load(Ordering::Relaxed)
+    }
+
+    /// 所有 E 站账号的名称和状态
+    pub fn accounts(&self) -> Vec<(String, AccountHealth)> {
+        self.ehentai.accounts()
+    }
+
+    /// 将账号的状态变化通知管理员
+    async fn report_accounts(&self) {
+        let events = self.ehentai.take_account_events();
+        if !events.is_empty() {
+            self.notify_admins(&events.join("\n")).await;
+        }
+    }
+
+    /// 被封禁时通知管理员，同一次封禁只通知一次，恢复正常后重置
+    async fn report_blocked(&self, blocked: Option<String>) {
+        match blocked {
+            Some(reason) => {
+                warn!("E 站访问受限，暂停本次扫描：{}", reason);
+                if !self.blocked.swap(true, Ordering::Relaxed) {
+                    self.notify_admins(&format!(
+                        "E 站访问受限，所有账号都不可用，已暂停本次扫描，将在下次扫描时重试\n\n原因: {}\n\n如果是 cookie 失效，请更新配置文件中的 cookie",
+                        reason
+                    ))
+                    .await;
+                }
+            }
+            None => {
+                if self.blocked.swap(false, Ordering::Relaxed) {
+                    self.notify_admins("E 站访问已恢复正常").await;
+                }
+            }
+        }
+    }
+
+    /// 通知所有管理员
+    async fn notify_admins(&self, message: &str) {
+        for user_id in &self.config.telegram.trusted_users {
+            if let Ok(chat_id) = user_id.parse::<i64>() {
+                let result = self.bot.send_message(ChatId(chat_id), message).await;
+                if let Err(e) = result {
+                    error!("向管理员 {} 发送通知失败: {}", user_id, e);
+                }
+            }
+        }
+    }
+}
+
+async fn flatten<T>(handle: JoinHandle<Result<T, UploadError>>) -> Result<T, UploadError> {
+    // 任务 panic 时 JoinError 会被转换为 UploadError::Aborted
+    handle.await?
+}
+
+impl ExloliUploader {
+    /// 重新扫描并上传没有上传过但存在记录的画廊
+    pub async fn reupload(&self, mut galleries: Vec<GalleryEntity>) -> Result<()> {
+        if galleries.is_empty() {
+            galleries = GalleryEntity::list_scans().await?;
+        }
+        let profile = self.config.default_profile();
+        for gallery in galleries.iter().rev() {
+            if let Some(score) = PollEntity::get_by_gallery(&profile.channel_key(), gallery.id).await? {
+                if score.score > 0.8 {
+                    info!("尝试上传画廊：{}", gallery.url());
+                    if let Err(err) = self.try_upload(&gallery.url(), true, &profile).await {
+                        error!("上传失败：{}", err);
+                    }
+                    time::sleep(Duration::from_secs(60)).await;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 重新下载画廊中缺失的页面，有新的页面补上时重新发布文章，返回是否重新发布
+    async fn fill_missing_pages(
+        &self,
+        gallery: &GalleryEntity,
+        before: usize,
+    ) -> Result<bool> {
+        let eh_gallery = self.ehentai.get_gallery(&gallery.url()).await?;
+        // 已上传的页面会被跳过，只下载缺失的部分
+        self.upload_gallery_image_with_progress(
+            &eh_gallery,
+            true,
+            None::<fn(UploadProgress) -> std::future::Ready<()>>,
+        )
+        .await?;
+        let after = MissingPageEntity::get_by_gallery(gallery.id).await?.len();
+        if after >= before {
+            return Ok(false);
+        }
+        info!("已补充 {} 个缺失的页面，剩余 {} 个", before - after, after);
+        self.republish(gallery).await?;
+        time::sleep(Duration::from_secs(60)).await;
+        Ok(true)
+    }
+
+    /// 重新检测已上传过的画廊预览是否有效，并重新上传
+    pub async fn recheck(&self, mut galleries: Vec<GalleryEntity>) -> Result<()> {
+        if galleries.is_empty() {
+            galleries = GalleryEntity::list_scans().await?;
+            // 存在缺失页面的画廊也需要检查
+            for id in MissingPageEntity::list_galleries().await? {
+                if galleries.iter().all(|g| g.id != id) {
+                    galleries.extend(GalleryEntity::get(id).await?);
+                }
+            }
+        }
+        for gallery in galleries.iter().rev() {
+            let telegraph =
+                TelegraphEntity::get(gallery.id).await?.ok_or(anyhow!("找不到 telegraph"))?;
+            if !MessageEntity::list_by_gallery(gallery.id).await?.is_empty() {
+                info!("检测画廊：{}", gallery.url());
+                let missing = MissingPageEntity::get_by_gallery(gallery.id).await?;
+                let mut republished = false;
+                if !missing.is_empty() {
+                    info!("补充 {} 个缺失的页面：{}", missing.len(), gallery.url());
+                    match self.fill_missing_pages(gallery, missing.len()).await {
+                        Ok(v) => republished = v,
+                        Err(err) => error!("补充缺失页面失败：{}", err),
+                    }
+                }
+                if !republished
+                    && (!self.check_telegraph(&telegraph.url).await?
+                        || self.need_switch_mirror(gallery.id).await?)
+                {
+                    info!("重新上传预览：{}", gallery.url());
+                    if let Err(err) = self.republish(gallery).await {
+                        error!("上传失败：{}", err);
+                    }
+                    time::sleep(Duration::from_secs(60)).await;
+                }
+            }
+            time::sleep(Duration::from_secs(1)).await;
+        }
+        Ok(())
+    }
+}
+
+/// 查找投票时父画廊链上的一步
+enum Ancestor {
+    /// 该画廊在频道中已经有投票
+    Poll(i64),
+    /// 该画廊没有投票，继续查找它的父画廊
+    Parent(EhGalleryUrl),
+    /// 已经到达根画廊
+    Root,
+}
+
+/// 从 start 开始沿着父画廊向上查找投票，层数不限，出现循环时由 visited 终止
+///
+/// 父画廊出现循环、到达根画廊或者查找出错时返回 None
+async fn find_ancestor_poll<F, Fut>(start: EhGalleryUrl, mut step: F) -> Option<i64>
+where
+    F: FnMut(EhGalleryUrl) -> Fut,
+    Fut: std::future::Future<Output = Result<Ancestor>>,
+{
+    let id = start.id();
+    let mut visited = HashSet::from([id]);
+    let mut current = start;
+    loop {
+        let url = current.url();
+        match step(current).await {
+            Ok(Ancestor::Poll(poll)) => return Some(poll),
+            Ok(Ancestor::Root) => return None,
+            Ok(Ancestor::Parent(parent)) => {
+                if !visited.insert(parent.id()) {
+                    warn!("画廊 {} 的父画廊形成了循环", id);
+                    return None;
+                }
+                current = parent;
+            }
+            Err(e) => {
+                warn!("查找画廊 {} 的父画廊失败，使用新的投票：{:?}", url, e);
+                return None;
+            }
+        }
+    }
+}
+
+/// 从搜索结果中选出比扫描进度 mark 更新的画廊，最多 count 个
+///
+/// 首次扫描时只取最新的 count 个画廊；新画廊超过 count 个时先处理最早的那部分，
+/// 进度只推进到已处理的画廊，剩下的留到下次扫描，避免被跳过
+async fn select_new<S>(search: S, mark: Option<i32>, count: usize) -> Vec<EhGalleryUrl>
+where
+    S: futures::Stream<Item = EhGalleryUrl>,
+{
+    match mark {
+        None => search.take(count).collect().await,
+        Some(mark) => {
+            let mut newer = search
+                .take_while(|url| future::ready(url.id() > mark))
+                .collect::<Vec<_>>()
+                .await;
+            info!("发现 {} 个新画廊，上次扫描到 {}", newer.len(), mark);
+            newer.split_off(newer.len().saturating_sub(count))
+        }
+    }
+}
+
+/// 扫描完 batch 中的画廊之后新的扫描进度，batch 为空时返回 None
+///
+/// 进度停在最早一个被推迟的画廊之前，这样下次扫描时还能看到它，
+/// 不在 batch 中的画廊（比如到期重试的失败画廊）由失败记录负责，不影响进度
+fn next_mark(batch: &[i32], held: &[i32]) -> Option<i32> {
+    match held.iter().filter(|id| batch.contains(id)).min() {
+        Some(floor) => Some(floor - 1),
+        None => batch.iter().max().copied(),
+    }
+}
+
+/// 按页码将占位图片与已有的图片合并，`pages` 为 `images` 中每张图片对应的页码
+fn merge_placeholders(
+    pages: &[i32],
+    images: Vec<String>,
+    missing: &[i32],
+    placeholder: &str,
+) -> Vec<String> {
+    let mut merged = pages.iter().copied().zip(images).collect::<Vec<_>>();
+    merged.extend(missing.iter().map(|&page| (page, placeholder.to_owned())));
+    merged.sort_by_key(|(page, _)| *page);
+    merged.into_iter().map(|(_, url)| url).collect()
+}
+
+/// 根据画廊发布了多久决定检查更新的间隔
+///
+/// 2 天内发布的画廊，每天检查一次；7 天内的每 3 天一次；14 天内的每 7 天一次；其余的每 14 天一次
+fn check_interval(age: chrono::Duration) -> chrono::Duration {
+    let days = match age {
+        d if d < chrono::Duration::days(2) => 1,
+        d if d < chrono::Duration::days(7) => 3,
+        d if d < chrono::Duration::days(14) => 7,
+        _ => 14,
+    };
+    chrono::Duration::days(days)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_interval_by_age() {
+        let days = |d| check_interval(chrono::Duration::days(d)).num_days();
+        assert_eq!(days(0), 1);
+        assert_eq!(days(2), 3);
+        assert_eq!(days(6), 3);
+        assert_eq!(days(7), 7);
+        assert_eq!(days(30), 14);
+    }
+
+    #[test]
+    fn placeholders_by_page_number() {
+        let images = vec!["1".to_owned(), "2".to_owned(), "5".to_owned(), "6".to_owned()];
+        // 第 3 页是广告没有记录，第 4、7 页下载失败
+        let merged = merge_placeholders(&[1, 2, 5, 6], images, &[4, 7], "x");
+        assert_eq!(merged, ["1", "2", "x", "5", "6", "x"]);
+    }
+
+    fn gallery(id: i32) -> EhGalleryUrl {
+        format!("https://exhentai.org/g/{}/abcdef0123/", id).parse().unwrap()
+    }
+
+    /// 用 (画廊, 父画廊) 列表模拟父画廊链，polls 中的画廊有投票，不在列表中的画廊查找失败
+    async fn walk(parents: &[(i32, Option<i32>)], polls: &[(i32, i64)], start: i32) -> Option<i64> {
+        find_ancestor_poll(gallery(start), |url| {
+            let id = url.id();
+            let result = match polls.iter().find(|(g, _)| *g == id) {
+                Some((_, poll)) => Ok(Ancestor::Poll(*poll)),
+                None => match parents.iter().find(|(g, _)| *g == id) {
+                    Some((_, Some(parent))) => Ok(Ancestor::Parent(gallery(*parent))),
+                    Some((_, None)) => Ok(Ancestor::Root),
+                    None => Err(anyhow!("network error")),
+                },
+            };
+            future::ready(result)
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn ancestor_poll() {
+        let chain = [(3, Some(2)), (2, Some(1)), (1, None)];
+        assert_eq!(walk(&chain, &[(1, 100)], 3).await, Some(100));
+        assert_eq!(walk(&chain, &[], 3).await, None);
+        // 父画廊形成循环
+        assert_eq!(walk(&[(3, Some(2)), (2, Some(3))], &[], 3).await, None);
+        // 查找父画廊失败时放弃，祖先画廊的投票不会被找到
+        assert_eq!(walk(&[(3, Some(2))], &[(1, 100)], 3).await, None);
+        // 很长的版本链也能找到最早版本的投票
+        let mut long = (1..40).map(|i| (i + 1, Some(i))).collect::<Vec<_>>();
+        long.push((1, None));
+        assert_eq!(walk(&long, &[(1, 100)], 40).await, Some(100));
+    }
+
+    #[tokio::test]
+    async fn postponed_gallery_scanned_again() {
+        let search = || futures::stream::iter((1..=10).rev().map(gallery));
+        let ids = |urls: Vec<EhGalleryUrl>| urls.iter().map(|g| g.id()).collect::<Vec<_>>();
+
+        let batch = ids(select_new(search(), Some(4), 3).await);
+        assert_eq!(batch, [7, 6, 5]);
+        // 画廊 6 因为额度不足被推迟，进度只能推进到 5
+        let mark = next_mark(&batch, &[6]);
+        assert_eq!(mark, Some(5));
+        assert_eq!(ids(select_new(search(), mark, 3).await), [8, 7, 6]);
+
+        // 最早的画廊被推迟时进度停在它之前
+        assert_eq!(next_mark(&batch, &[5, 7]), Some(4));
+        // 没有推迟的画廊，或者被推迟的是到期重试的旧画廊时，推进到本批次最大的画廊
+        assert_eq!(next_mark(&batch, &[]), Some(7));
+        assert_eq!(next_mark(&batch, &[2]), Some(7));
+        assert_eq!(ids(select_new(search(), None, 3).await), [10, 9, 8]);
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use futures::future::BoxFuture;
+use tokio::sync::{oneshot, Mutex, Notify};
+
+use super::{UploadError, UploadProgress};
+use crate::database::UploadJobEntity;
+use crate::ehentai::{EhGallery, EhGalleryUrl};
+
+/// 上传进度回调，放入队列之后由 worker 调用
+pub type ProgressCallback = Box<dyn Fn(UploadProgress) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// 等待上传任务结果的调用者
+struct Waiter {
+    /// 调用者已经获取过的画廊信息，worker 可以直接使用
+    gallery: Option<EhGallery>,
+    progress: Option<ProgressCallback>,
+    result: oneshot::Sender<Result<(), UploadError>>,
+}
+
+/// 上传任务队列
+///
+/// 任务本身保存在 upload_job 表中，重启后由 worker 继续执行，这里只记录正在等待结果的调用者
+#[derive(Default)]
+pub struct UploadQueue {
+    notify: Notify,
+    waiters: Mutex<HashMap<i64, Vec<Waiter>>>,
+}
+
+impl fmt::Debug for UploadQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UploadQueue").finish_non_exhaustive()
+    }
+}
+
+impl UploadQueue {
+    /// 将画廊加入队列并唤醒 worker，返回的 receiver 在任务结束时收到结果
+    pub async fn push(
+        &self,
+        gallery: &EhGalleryUrl,
+        check: bool,
+        channel: &str,
+        reply_to: Option<i32>,
+        prefetched: Option<EhGallery>,
+        progress: Option<ProgressCallback>,
+    ) -> Result<oneshot::Receiver<Result<(), UploadError>>, UploadError> {
+        // 持有锁直到登记完成，避免 worker 在登记之前就执行完这个任务
+        let mut waiters = self.waiters.lock().await;
+        let job = UploadJobEntity::push(gallery, check, channel, reply_to).await?;
+        let (tx, rx) = oneshot::channel();
+        waiters.entry(job.id).or_default().push(Waiter { gallery: prefetched, progress, result: tx });
+        drop(waiters);
+        self.notify.notify_one();
+        Ok(rx)
+    }
+
+    /// 等待新的任务加入队列
+    pub async fn wait(&self) {
+        self.notify.notified().await
+    }
+
+    /// 任务开始执行时，取出调用者提供的画廊信息和进度回调
+    pub async fn take(&self, id: i64) -> (Option<EhGallery>, Option<ProgressCallback>) {
+        let mut waiters = self.waiters.lock().await;
+        let Some(waiters) = waiters.get_mut(&id) else {
+            return (None, None);
+        };
+        let gallery = waiters.iter_mut().find_map(|w| w.gallery.take());
+        let progress = waiters.iter_mut().find_map(|w| w.progress.take());
+        (gallery, progress)
+    }
+
+    /// 任务结束，将结果发送给所有等待的调用者
+    ///
+    /// 同一个任务有多个调用者时，只有第一个能拿到原始的错误
+    pub async fn finish(&self, id: i64, result: Result<(), UploadError>) {
+        let waiters = self.waiters.lock().await.remove(&id).unwrap_or_default();
+        let shared = result.as_ref().map(|_| ()).map_err(|e| e.to_string());
+        let mut result = Some(result);
+        for waiter in waiters {
+            let result = result.take().unwrap_or_else(|| shared.clone().map_err(UploadError::Job));
+            // 调用者已经不再等待时忽略
+            let _ = waiter.result.send(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn finish_notifies_every_waiter() {
+        let queue = UploadQueue::default();
+        let mut receivers = vec![];
+        for _ in 0..2 {
+            let (tx, rx) = oneshot::channel();
+            let waiter = Waiter { gallery: None, progress: None, result: tx };
+            queue.waiters.lock().await.entry(1).or_default().push(waiter);
+            receivers.push(rx);
+        }
+        queue.finish(1, Err(UploadError::Telegraph(anyhow::anyhow!("timeout")))).await;
+        assert!(queue.waiters.lock().await.is_empty());
+
+        let first = receivers.remove(0).await.unwrap();
+        assert!(matches!(first, Err(UploadError::Telegraph(_))));
+        let second = receivers.remove(0).await.unwrap();
+        assert!(matches!(second, Err(UploadError::Job(e)) if e.contains("timeout")));
+    }
+}
@@ -36,3 +36,8 @@ impl<'a> SelectorExtend for ElementRef<'a> {
         self.select(selector)
     }
 }
+
+/// 还原 HTML 实体，例如 API 返回的标题中的 &amp;amp;
+pub fn unescape(s: &str) -> String {
+    Html::parse_fragment(s).root_element().text().collect()
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use reqwest::{Client, ClientBuilder, Proxy};
+
+use crate::config::Config;
+
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+/// HTTP 客户端的用途，不同用途可以使用不同的代理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientKind {
+    /// 访问 E 站页面和 API
+    Site,
+    /// 下载图片，未单独配置时和 Site 使用同一个代理
+    Image,
+    /// 图床、翻译数据库等其他请求
+    Other,
+}
+
+/// 统一创建 HTTP 客户端，负责设置代理、超时和 UA
+///
+/// NOTE: S3 图床使用 rust-s3 自带的客户端，不支持代理
+#[derive(Debug, Clone, Default)]
+pub struct HttpFactory {
+    proxy: Option<String>,
+    image_proxy: Option<String>,
+}
+
+impl HttpFactory {
+    pub fn new(config: &Config) -> Self {
+        Self { proxy: config.proxy.clone(), image_proxy: config.image_proxy.clone() }
+    }
+
+    /// 替换访问 E 站时使用的代理，为 None 时保持不变
+    pub fn with_proxy(&self, proxy: Option<&str>) -> Self {
+        match proxy {
+            Some(proxy) => Self { proxy: Some(proxy.to_string()), ..self.clone() },
+            None => self.clone(),
+        }
+    }
+
+    /// 返回已经设置好代理等参数的 ClientBuilder，可以在此基础上添加 cookie 等设置
+    pub fn builder(&self, kind: ClientKind) -> Result<ClientBuilder> {
+        let timeout = match kind {
+            ClientKind::Site => Duration::from_secs(30),
+            // 上传大图片需要更长的时间
+            ClientKind::Other => Duration::from_secs(120),
+            // 通过较慢的代理下载大尺寸原图可能需要好几分钟，卡住的连接交给 read_timeout 处理
+            ClientKind::Image => Duration::from_secs(600),
+        };
+        let mut builder = Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(timeout)
+            .read_timeout(Duration::from_secs(60))
+            .connect_timeout(Duration::from_secs(30));
+        if let Some(proxy) = self.proxy(kind) {
+            // 支持 http、https 以及 socks5 代理
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+        Ok(builder)
+    }
+
+    pub fn client(&self, kind: ClientKind) -> Result<Client> {
+        Ok(self.builder(kind)?.build()?)
+    }
+
+    fn proxy(&self, kind: ClientKind) -> Option<&str> {
+        match kind {
+            ClientKind::Image => self.image_proxy.as_deref().or(self.proxy.as_deref()),
+            ClientKind::Site | ClientKind::Other => self.proxy.as_deref(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_proxy_fallback() {
+        let http = HttpFactory { proxy: Some("socks5://127.0.0.1:1080".into()), image_proxy: None };
+        assert_eq!(http.proxy(ClientKind::Image), Some("socks5://127.0.0.1:1080"));
+        let http = HttpFactory { image_proxy: Some("http://127.0.0.1:8080".into()), ..http };
+        assert_eq!(http.proxy(ClientKind::Image), Some("http://127.0.0.1:8080"));
+        assert_eq!(http.proxy(ClientKind::Site), Some("socks5://127.0.0.1:1080"));
+        assert!(http.client(ClientKind::Site).is_ok());
+        assert!(HttpFactory { proxy: Some("not a url".into()), image_proxy: None }
+            .client(ClientKind::Other)
+            .is_err());
+    }
+}
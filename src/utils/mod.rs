@@ -1,9 +1,15 @@
 use std::borrow::Cow;
 
+use anyhow::{ensure, Result};
+use image::EncodableLayout;
+use sha1::{Digest, Sha1};
+
 pub mod html;
+pub mod http;
+pub mod transcode;
 
 /// 左填充空格
-pub fn pad_left(s: &str, len: usize) -> Cow<str> {
+pub fn pad_left(s: &str, len: usize) -> Cow<'_, str> {
     let width = unicode_width::UnicodeWidthStr::width(s);
     if width >= len {
         Cow::Borrowed(s)
@@ -11,3 +17,40 @@ pub fn pad_left(s: &str, len: usize) -> Cow<str> {
         Cow::Owned(" ".repeat(len - width) + s)
     }
 }
+
+/// 检测图片中是否含有二维码，含有二维码的图片大概率是广告
+pub fn has_qrcode(data: &[u8]) -> Result<bool> {
+    let image = image::load_from_memory(data)?.into_luma8();
+    let mut decoder = quircs::Quirc::default();
+    let codes = decoder.identify(image.width() as usize, image.height() as usize, image.as_bytes());
+    Ok(codes.count() > 0)
+}
+
+/// 校验下载到的图片是否完整
+///
+/// 页面 URL 中的 hash 是原图 sha1 的前 10 位，原图可以直接比较 hash；
+/// 缩放过的图片和预览图没有可以比较的 hash，只能尝试解码一次
+pub fn verify_image(data: &[u8], hash: Option<&str>) -> Result<()> {
+    match hash {
+        Some(hash) => {
+            let digest = format!("{:x}", Sha1::digest(data));
+            ensure!(digest.starts_with(hash), "图片 hash 不匹配：期望 {}，实际 {}", hash, digest);
+        }
+        None => {
+            image::load_from_memory(data)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_image_hash() {
+        assert!(verify_image(b"abc", Some("a9993e3647")).is_ok());
+        assert!(verify_image(b"abd", Some("a9993e3647")).is_err());
+        assert!(verify_image(b"<html></html>", None).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use std::io::Cursor;
+
+use anyhow::{ensure, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::ImageEncoder;
+
+use crate::config::{Transcode, TranscodeFormat};
+
+/// 重新编码后的图片
+#[derive(Debug)]
+pub struct Transcoded {
+    /// 编码后的数据
+    pub data: Vec<u8>,
+    /// 对应的文件后缀
+    pub ext: &'static str,
+}
+
+/// 将图片缩放到限制尺寸内并重新编码
+///
+/// 编码结果会被重新解码一次，确认其可以正常读取并且尺寸正确。
+/// 该函数是 CPU 密集的，在异步上下文中请放入 spawn_blocking 执行
+pub fn transcode(data: &[u8], config: &Transcode) -> Result<Transcoded> {
+    let mut img = image::load_from_memory(data)?;
+    if img.width() > config.max_dimension || img.height() > config.max_dimension {
+        img = img.resize(config.max_dimension, config.max_dimension, FilterType::Lanczos3);
+    }
+
+    let mut buf = Cursor::new(vec![]);
+    let ext = match config.format {
+        TranscodeFormat::Jpeg => {
+            let rgb = img.to_rgb8();
+            JpegEncoder::new_with_quality(&mut buf, config.quality).write_image(
+                &rgb,
+                rgb.width(),
+                rgb.height(),
+                image::ExtendedColorType::Rgb8,
+            )?;
+            "jpg"
+        }
+        TranscodeFormat::WebP => {
+            let rgba = img.to_rgba8();
+            WebPEncoder::new_lossless(&mut buf).write_image(
+                &rgba,
+                rgba.width(),
+                rgba.height(),
+                image::ExtendedColorType::Rgba8,
+            )?;
+            "webp"
+        }
+    };
+    let data = buf.into_inner();
+
+    // 校验编码结果
+    let check = image::load_from_memory(&data)?;
+    ensure!(
+        check.width() == img.width() && check.height() == img.height(),
+        "重新编码后的图片尺寸不一致：{}x{} -> {}x{}",
+        img.width(),
+        img.height(),
+        check.width(),
+        check.height()
+    );
+
+    Ok(Transcoded { data, ext })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, ImageFormat, RgbImage};
+
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([(x % 256) as u8, (y % 256) as u8, 128])
+        }));
+        let mut buf = Cursor::new(vec![]);
+        img.write_to(&mut buf, ImageFormat::Png).unwrap();
+        buf.into_inner()
+    }
+
+    #[test]
+    fn transcode_resize() {
+        let config = Transcode { max_dimension: 100, ..Default::default() };
+        let result = transcode(&png(400, 200), &config).unwrap();
+        assert_eq!(result.ext, "jpg");
+        let img = image::load_from_memory(&result.data).unwrap();
+        assert_eq!((img.width(), img.height()), (100, 50));
+    }
+
+    #[test]
+    fn transcode_webp() {
+        let config = Transcode { format: TranscodeFormat::WebP, ..Default::default() };
+        let result = transcode(&png(64, 64), &config).unwrap();
+        assert_eq!(result.ext, "webp");
+        assert_eq!(image::guess_format(&result.data).unwrap(), ImageFormat::WebP);
+    }
+
+    #[test]
+    fn transcode_invalid() {
+        assert!(transcode(b"<html></html>", &Transcode::default()).is_err());
+    }
+}